@@ -0,0 +1,15 @@
+-- 0006_create_tangle_pending_blocks.sql
+-- Quarantine for tangle blocks whose parents have not been stored yet
+
+CREATE TABLE IF NOT EXISTS public.tangle_pending_blocks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    parents TEXT[] NOT NULL,
+    missing_parents TEXT[] NOT NULL,
+    data JSONB NOT NULL,
+    signature BYTEA NOT NULL,
+    public_key TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- GIN index so a newly stored block can find the pending blocks waiting on it
+CREATE INDEX IF NOT EXISTS idx_tangle_pending_blocks_missing_gin ON public.tangle_pending_blocks USING GIN (missing_parents);
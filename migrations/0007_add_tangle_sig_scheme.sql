@@ -0,0 +1,9 @@
+-- 0007_add_tangle_sig_scheme.sql
+-- Record which signing payload a block signature covers.
+-- 0 = legacy (data only), 1 = canonical v1 (id, parents, data, timestamp_ms)
+
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS sig_scheme SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS timestamp_ms BIGINT;
+
+ALTER TABLE public.tangle_pending_blocks ADD COLUMN IF NOT EXISTS sig_scheme SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE public.tangle_pending_blocks ADD COLUMN IF NOT EXISTS timestamp_ms BIGINT;
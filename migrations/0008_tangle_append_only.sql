@@ -0,0 +1,38 @@
+-- 0008_tangle_append_only.sql
+-- Blocks are immutable once stored. Corrections are new blocks that
+-- supersede (or tombstone) an earlier block signed by the same key.
+
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS supersedes UUID REFERENCES public.tangle_blocks (id);
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS tombstone BOOLEAN NOT NULL DEFAULT false;
+
+-- a block can be superseded at most once, so every chain has a single head
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tangle_blocks_supersedes_unique ON public.tangle_blocks (supersedes) WHERE supersedes IS NOT NULL;
+
+ALTER TABLE public.tangle_pending_blocks ADD COLUMN IF NOT EXISTS supersedes UUID;
+ALTER TABLE public.tangle_pending_blocks ADD COLUMN IF NOT EXISTS tombstone BOOLEAN NOT NULL DEFAULT false;
+
+CREATE OR REPLACE FUNCTION public.tangle_blocks_immutable() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        RAISE EXCEPTION 'tangle blocks are append-only';
+    END IF;
+    IF NEW.id IS DISTINCT FROM OLD.id
+        OR NEW.parents IS DISTINCT FROM OLD.parents
+        OR NEW.data IS DISTINCT FROM OLD.data
+        OR NEW.signature IS DISTINCT FROM OLD.signature
+        OR NEW.public_key IS DISTINCT FROM OLD.public_key
+        OR NEW.created_at IS DISTINCT FROM OLD.created_at
+        OR NEW.sig_scheme IS DISTINCT FROM OLD.sig_scheme
+        OR NEW.timestamp_ms IS DISTINCT FROM OLD.timestamp_ms
+        OR NEW.supersedes IS DISTINCT FROM OLD.supersedes
+        OR NEW.tombstone IS DISTINCT FROM OLD.tombstone THEN
+        RAISE EXCEPTION 'tangle blocks are append-only';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tangle_blocks_immutable ON public.tangle_blocks;
+CREATE TRIGGER tangle_blocks_immutable
+    BEFORE UPDATE OR DELETE ON public.tangle_blocks
+    FOR EACH ROW EXECUTE FUNCTION public.tangle_blocks_immutable();
@@ -0,0 +1,33 @@
+-- 0009_tangle_content_hash.sql
+-- SHA-256 of the canonical block content (everything but the id), so nodes
+-- agree on the identity of identical blocks. Rows stored before this
+-- migration keep a NULL hash.
+
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS hash BYTEA;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tangle_blocks_hash_unique ON public.tangle_blocks (hash) WHERE hash IS NOT NULL;
+
+ALTER TABLE public.tangle_pending_blocks ADD COLUMN IF NOT EXISTS hash BYTEA;
+CREATE INDEX IF NOT EXISTS idx_tangle_pending_blocks_hash ON public.tangle_pending_blocks (hash);
+
+-- a hash may be filled in once but never changed
+CREATE OR REPLACE FUNCTION public.tangle_blocks_immutable() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        RAISE EXCEPTION 'tangle blocks are append-only';
+    END IF;
+    IF NEW.id IS DISTINCT FROM OLD.id
+        OR NEW.parents IS DISTINCT FROM OLD.parents
+        OR NEW.data IS DISTINCT FROM OLD.data
+        OR NEW.signature IS DISTINCT FROM OLD.signature
+        OR NEW.public_key IS DISTINCT FROM OLD.public_key
+        OR NEW.created_at IS DISTINCT FROM OLD.created_at
+        OR NEW.sig_scheme IS DISTINCT FROM OLD.sig_scheme
+        OR NEW.timestamp_ms IS DISTINCT FROM OLD.timestamp_ms
+        OR NEW.supersedes IS DISTINCT FROM OLD.supersedes
+        OR NEW.tombstone IS DISTINCT FROM OLD.tombstone
+        OR (OLD.hash IS NOT NULL AND NEW.hash IS DISTINCT FROM OLD.hash) THEN
+        RAISE EXCEPTION 'tangle blocks are append-only';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
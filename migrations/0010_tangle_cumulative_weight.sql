@@ -0,0 +1,17 @@
+-- 0010_tangle_cumulative_weight.sql
+-- Number of blocks that directly or indirectly approve each block. Maintained
+-- incrementally by the service on insert; the column is outside the
+-- append-only guard so it can be updated.
+
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS cumulative_weight BIGINT NOT NULL DEFAULT 0;
+
+UPDATE public.tangle_blocks b SET cumulative_weight = (
+    WITH RECURSIVE des(id) AS (
+        SELECT b.id
+        UNION
+        SELECT t.id FROM public.tangle_blocks t JOIN des d ON t.parents @> ARRAY[d.id::text]
+    )
+    SELECT COUNT(*) - 1 FROM des
+);
+
+CREATE INDEX IF NOT EXISTS idx_tangle_blocks_cumulative_weight ON public.tangle_blocks (cumulative_weight);
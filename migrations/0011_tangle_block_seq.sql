@@ -0,0 +1,6 @@
+-- 0011_tangle_block_seq.sql
+-- Insertion order of stored blocks. Parents are always stored before their
+-- children, so ordering by seq yields a topological order for export.
+
+ALTER TABLE public.tangle_blocks ADD COLUMN IF NOT EXISTS seq BIGINT GENERATED ALWAYS AS IDENTITY;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tangle_blocks_seq ON public.tangle_blocks (seq);
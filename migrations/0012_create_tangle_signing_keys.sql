@@ -0,0 +1,14 @@
+-- 0012_create_tangle_signing_keys.sql
+-- ed25519 keys a user has proven to hold. A key is bound to one user for
+-- good; revoking it does not free it for someone else.
+
+CREATE TABLE IF NOT EXISTS public.tangle_signing_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES public.users (id) ON DELETE CASCADE,
+    public_key TEXT NOT NULL UNIQUE,
+    label TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_tangle_signing_keys_user ON public.tangle_signing_keys (user_id);
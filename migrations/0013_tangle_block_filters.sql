@@ -0,0 +1,7 @@
+-- 0013_tangle_block_filters.sql
+-- Indexes backing the payload, public key and time range filters of
+-- GET /tangle/blocks.
+
+-- jsonb_path_ops only supports containment (@>) but is much smaller than the default opclass
+CREATE INDEX IF NOT EXISTS idx_tangle_blocks_data_gin ON public.tangle_blocks USING GIN (data jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS idx_tangle_blocks_public_key_created_at ON public.tangle_blocks (public_key, created_at DESC);
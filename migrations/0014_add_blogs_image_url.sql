@@ -0,0 +1,4 @@
+-- 0014_add_blogs_image_url.sql
+-- The blog handlers read and write image_url but no migration created it.
+
+ALTER TABLE blogs ADD COLUMN IF NOT EXISTS image_url TEXT;
@@ -0,0 +1,7 @@
+-- 0015_keyset_pagination_indexes.sql
+-- Listings page on (created_at, id) so that cursors stay stable when rows
+-- share a timestamp.
+
+CREATE INDEX IF NOT EXISTS blogs_created_at_id_idx ON blogs (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS stories_created_at_id_idx ON stories (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_tangle_blocks_created_at_id ON public.tangle_blocks (created_at DESC, id DESC);
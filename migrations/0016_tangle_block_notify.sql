@@ -0,0 +1,16 @@
+-- 0016_tangle_block_notify.sql
+-- Announces every stored block on the tangle_blocks channel once its
+-- transaction commits. The payload is the block's seq; listeners load the
+-- row themselves since NOTIFY payloads are capped at 8000 bytes.
+
+CREATE OR REPLACE FUNCTION public.tangle_blocks_notify() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('tangle_blocks', NEW.seq::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tangle_blocks_notify ON public.tangle_blocks;
+CREATE TRIGGER tangle_blocks_notify
+    AFTER INSERT ON public.tangle_blocks
+    FOR EACH ROW EXECUTE FUNCTION public.tangle_blocks_notify();
@@ -0,0 +1,28 @@
+-- 0017_create_tangle_checkpoints.sql
+-- Merkle checkpoints over the blocks stored since the previous checkpoint,
+-- by seq range (from_seq, to_seq]. Each checkpoint chains to the one before
+-- it, see crypto::checkpoint_chain_hash.
+
+CREATE TABLE IF NOT EXISTS public.tangle_checkpoints (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    from_seq BIGINT NOT NULL UNIQUE,
+    to_seq BIGINT NOT NULL,
+    block_count BIGINT NOT NULL,
+    root BYTEA NOT NULL,
+    chain BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (to_seq > from_seq)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tangle_checkpoints_to_seq ON public.tangle_checkpoints (to_seq);
+
+CREATE OR REPLACE FUNCTION public.tangle_checkpoints_immutable() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'tangle checkpoints are append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tangle_checkpoints_immutable ON public.tangle_checkpoints;
+CREATE TRIGGER tangle_checkpoints_immutable
+    BEFORE UPDATE OR DELETE ON public.tangle_checkpoints
+    FOR EACH ROW EXECUTE FUNCTION public.tangle_checkpoints_immutable();
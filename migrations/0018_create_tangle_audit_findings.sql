@@ -0,0 +1,19 @@
+-- 0018_create_tangle_audit_findings.sql
+-- Problems the background auditor found when re-checking stored blocks.
+-- One row per block and kind; a finding is resolved once the block passes
+-- that check again.
+
+CREATE TABLE IF NOT EXISTS public.tangle_audit_findings (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    block_id UUID NOT NULL,
+    seq BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMPTZ,
+    UNIQUE (block_id, kind)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tangle_audit_findings_open
+    ON public.tangle_audit_findings (kind, seq) WHERE resolved_at IS NULL;
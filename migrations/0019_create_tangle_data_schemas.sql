@@ -0,0 +1,11 @@
+-- 0019_create_tangle_data_schemas.sql
+-- JSON Schemas that block `data` is validated against, keyed by the
+-- payload's `type` field.
+
+CREATE TABLE IF NOT EXISTS public.tangle_data_schemas (
+    data_type TEXT PRIMARY KEY,
+    schema JSONB NOT NULL,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
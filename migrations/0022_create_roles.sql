@@ -0,0 +1,55 @@
+-- 0022_create_roles.sql
+-- Role-based access control. Permissions are `resource:action` names, the
+-- same shape as API key scopes; roles bundle them and users hold roles.
+-- `users.is_admin` is kept in step with the `admin` role.
+
+CREATE TABLE IF NOT EXISTS public.permissions (
+    name TEXT PRIMARY KEY,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS public.roles (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS public.role_permissions (
+    role TEXT NOT NULL REFERENCES public.roles (name) ON DELETE CASCADE,
+    permission TEXT NOT NULL REFERENCES public.permissions (name) ON DELETE CASCADE,
+    PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS public.user_roles (
+    user_id UUID NOT NULL REFERENCES public.users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL REFERENCES public.roles (name) ON DELETE CASCADE,
+    granted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, role)
+);
+
+INSERT INTO public.permissions (name, description) VALUES
+    ('users:admin', 'Create admins and assign roles'),
+    ('blog:write', 'Create, edit and delete blog posts'),
+    ('stories:write', 'Create, edit and delete stories'),
+    ('tangle:operate', 'Create checkpoints, import blocks and trigger sync')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.roles (name, description) VALUES
+    ('admin', 'Every permission'),
+    ('editor', 'Blog and stories authoring'),
+    ('operator', 'Tangle operations')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.role_permissions (role, permission)
+SELECT 'admin', name FROM public.permissions
+ON CONFLICT DO NOTHING;
+
+INSERT INTO public.role_permissions (role, permission) VALUES
+    ('editor', 'blog:write'),
+    ('editor', 'stories:write'),
+    ('operator', 'tangle:operate')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO public.user_roles (user_id, role)
+SELECT id, 'admin' FROM public.users WHERE is_admin
+ON CONFLICT DO NOTHING;
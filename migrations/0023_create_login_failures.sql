@@ -0,0 +1,15 @@
+-- 0023_create_login_failures.sql
+-- Audit trail of failed logins. `user_id` is set when the username exists;
+-- `reason` is one of bad_password, unknown_user or locked.
+
+CREATE TABLE IF NOT EXISTS public.login_failures (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL,
+    user_id UUID REFERENCES public.users (id) ON DELETE SET NULL,
+    ip TEXT,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_failures_username ON public.login_failures (username, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_login_failures_created ON public.login_failures (created_at DESC);
@@ -0,0 +1,23 @@
+-- 0024_create_user_mfa.sql
+-- TOTP second factor. A secret is pending until confirmed with a code
+-- (`enabled_at`); `last_used_step` keeps a code from being used twice.
+-- Recovery codes are stored as a SHA-256 and each works once.
+
+CREATE TABLE IF NOT EXISTS public.user_totp (
+    user_id UUID PRIMARY KEY REFERENCES public.users (id) ON DELETE CASCADE,
+    secret BYTEA NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS public.recovery_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES public.users (id) ON DELETE CASCADE,
+    code_hash BYTEA NOT NULL,
+    used_at TIMESTAMPTZ,
+    UNIQUE (user_id, code_hash)
+);
+
+-- Whether the session a refresh token belongs to was started with a second factor.
+ALTER TABLE public.refresh_tokens ADD COLUMN IF NOT EXISTS mfa BOOLEAN NOT NULL DEFAULT false;
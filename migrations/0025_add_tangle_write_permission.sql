@@ -0,0 +1,12 @@
+-- 0025_add_tangle_write_permission.sql
+-- Submitting blocks needs its own permission now that `POST /tangle/blocks`
+-- is no longer open; operators and admins hold it.
+
+INSERT INTO public.permissions (name, description) VALUES
+    ('tangle:write', 'Submit blocks to the tangle')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.role_permissions (role, permission) VALUES
+    ('admin', 'tangle:write'),
+    ('operator', 'tangle:write')
+ON CONFLICT DO NOTHING;
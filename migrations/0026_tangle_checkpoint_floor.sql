@@ -0,0 +1,21 @@
+-- 0026_tangle_checkpoint_floor.sql
+-- A block must not commit with a seq at or below the last checkpoint: the
+-- checkpoint's Merkle root would no longer match its seq range. Checked at
+-- commit, when the block's seq can no longer change. Raised as a
+-- serialization failure so the submission can simply be retried.
+
+CREATE OR REPLACE FUNCTION public.tangle_blocks_above_checkpoint() RETURNS trigger AS $$
+BEGIN
+    IF NEW.seq <= (SELECT COALESCE(MAX(to_seq), 0) FROM public.tangle_checkpoints) THEN
+        RAISE EXCEPTION 'tangle block seq % is already checkpointed', NEW.seq
+            USING ERRCODE = 'serialization_failure';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tangle_blocks_above_checkpoint ON public.tangle_blocks;
+CREATE CONSTRAINT TRIGGER tangle_blocks_above_checkpoint
+    AFTER INSERT ON public.tangle_blocks
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION public.tangle_blocks_above_checkpoint();
@@ -0,0 +1,11 @@
+-- 0027_create_revoked_access_tokens.sql
+-- Access tokens revoked by logout, by jti, kept until the token would have
+-- expired anyway. Revoked families are read from refresh_tokens.revoked_at.
+
+CREATE TABLE IF NOT EXISTS public.revoked_access_tokens (
+    jti TEXT PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_access_tokens_expires
+    ON public.revoked_access_tokens (expires_at);
@@ -7,6 +7,9 @@ pub trait Cache: Send + Sync + 'static {
     async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
     async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()>;
     async fn delete(&self, key: &str) -> anyhow::Result<()>;
+    /// Atomically adds one to the counter at `key`, starting from zero, and
+    /// returns the new value. `ttl` is reset on every increment.
+    async fn incr(&self, key: &str, ttl: Option<Duration>) -> anyhow::Result<u64>;
 }
 
 pub type DynCache = Arc<dyn Cache>;
@@ -17,9 +20,25 @@ mod inmem {
     use parking_lot::Mutex;
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
+    use std::time::Instant;
 
+    struct Entry {
+        value: Vec<u8>,
+        expires_at: Option<Instant>,
+    }
+
+    impl Entry {
+        fn new(value: Vec<u8>, ttl: Option<Duration>) -> Self {
+            Self {
+                value,
+                expires_at: ttl.map(|ttl| Instant::now() + ttl),
+            }
+        }
+    }
+
+    /// LRU cache whose entries also expire after their TTL, checked on read.
     pub struct InMemoryCache {
-        inner: Mutex<LruCache<u64, Vec<u8>>>,
+        inner: Mutex<LruCache<u64, Entry>>,
     }
 
     impl InMemoryCache {
@@ -36,23 +55,36 @@ mod inmem {
             key.hash(&mut h);
             h.finish()
         }
+
+        /// The entry at `k`, dropping it first if it has expired.
+        fn live(inner: &mut LruCache<u64, Entry>, k: u64) -> Option<&Entry> {
+            if inner
+                .peek(&k)
+                .and_then(|e| e.expires_at)
+                .is_some_and(|at| at <= Instant::now())
+            {
+                inner.pop(&k);
+            }
+            inner.get(&k)
+        }
     }
 
     #[async_trait]
     impl Cache for InMemoryCache {
         async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
             let k = Self::hash_key(key);
-            Ok(self.inner.lock().get(&k).cloned())
+            let mut inner = self.inner.lock();
+            Ok(Self::live(&mut inner, k).map(|e| e.value.clone()))
         }
 
         async fn set(
             &self,
             key: &str,
             value: Vec<u8>,
-            _ttl: Option<Duration>,
+            ttl: Option<Duration>,
         ) -> anyhow::Result<()> {
             let k = Self::hash_key(key);
-            self.inner.lock().put(k, value);
+            self.inner.lock().put(k, Entry::new(value, ttl));
             Ok(())
         }
 
@@ -61,6 +93,17 @@ mod inmem {
             self.inner.lock().pop(&k);
             Ok(())
         }
+
+        async fn incr(&self, key: &str, ttl: Option<Duration>) -> anyhow::Result<u64> {
+            let k = Self::hash_key(key);
+            let mut inner = self.inner.lock();
+            let count = Self::live(&mut inner, k)
+                .and_then(|e| std::str::from_utf8(&e.value).ok()?.parse::<u64>().ok())
+                .unwrap_or(0)
+                + 1;
+            inner.put(k, Entry::new(count.to_string().into_bytes(), ttl));
+            Ok(count)
+        }
     }
 
     impl InMemoryCache {
@@ -132,6 +175,17 @@ mod redis_backend {
             let _: () = guard.del(key).await?;
             Ok(())
         }
+
+        async fn incr(&self, key: &str, ttl: Option<std::time::Duration>) -> anyhow::Result<u64> {
+            let mut guard = self.conn.lock().await;
+            let mut pipe = redis::pipe();
+            pipe.atomic().cmd("INCR").arg(key);
+            if let Some(secs) = Self::ttl_to_redis_seconds(ttl) {
+                pipe.cmd("EXPIRE").arg(key).arg(secs as u64).ignore();
+            }
+            let (count,): (u64,) = pipe.query_async(&mut *guard).await?;
+            Ok(count)
+        }
     }
 
     impl RedisCache {
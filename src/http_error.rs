@@ -8,6 +8,8 @@ use sqlx::Error as SqlxError;
 struct ErrorBody {
     error: String,
     code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 
 #[derive(Debug)]
@@ -15,6 +17,8 @@ pub struct AppError {
     pub status: StatusCode,
     pub message: String,
     pub code: Option<String>,
+    /// Structured context for the client, e.g. field-level validation errors.
+    pub details: Option<serde_json::Value>,
 }
 
 impl AppError {
@@ -23,6 +27,7 @@ impl AppError {
             status,
             message: message.into(),
             code: None,
+            details: None,
         }
     }
 
@@ -30,6 +35,11 @@ impl AppError {
         self.code = Some(code.into());
         self
     }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
 }
 
 impl IntoResponse for AppError {
@@ -37,6 +47,7 @@ impl IntoResponse for AppError {
         let body = ErrorBody {
             error: self.message,
             code: self.code,
+            details: self.details,
         };
         (self.status, Json(body)).into_response()
     }
@@ -76,21 +87,27 @@ impl From<SqlxError> for AppError {
                                 status: StatusCode::CONFLICT,
                                 message: "duplicateKey".to_string(),
                                 code: Some(code_str.to_string()),
+                                details: None,
                             };
                         }
                         return AppError {
                             status: StatusCode::CONFLICT,
                             message: "duplicateKey".to_string(),
                             code: Some("duplicate_key".to_string()),
+                            details: None,
                         };
                     }
+                    // serialization failure or deadlock: retrying may succeed
+                    if code == "40001" || code == "40P01" {
+                        return AppError::new(StatusCode::CONFLICT, "transactionConflict")
+                            .with_code("transaction_conflict");
+                    }
                 }
-                
-                
-                
-                
-                
-                let code_or_unknown = db.code().map(|c| c.to_string()).unwrap_or_else(|| "<unknown>".to_string());
+
+                let code_or_unknown = db
+                    .code()
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "<unknown>".to_string());
                 AppError::new(
                     StatusCode::INTERNAL_SERVER_ERROR,
                     format!("databaseError (sqlstate={})", code_or_unknown),
@@ -1,4 +1,5 @@
 use crate::cache::DynCache;
+use crate::plugins::auth::policy::{self, Access, AccessRule, AuthPolicy};
 use crate::plugins::metrics::MetricsPlugin as MaybeMetrics;
 use async_trait::async_trait;
 use axum::Router;
@@ -8,12 +9,22 @@ use axum::http::{
     header::{HeaderValue, ORIGIN},
 };
 use axum::middleware::Next;
+use axum::response::IntoResponse;
 use tracing::info;
 
 #[async_trait]
 pub trait Plugin: Send + Sync {
     async fn router(&self) -> Router;
     fn name(&self) -> &'static str;
+    /// Access required by routes no entry of [`Plugin::access_rules`] matches.
+    fn default_access(&self) -> Access {
+        Access::ApiKey
+    }
+    /// Per-route access, matched on the route pattern relative to the
+    /// plugin's mount point. The first matching rule wins.
+    fn access_rules(&self) -> Vec<AccessRule> {
+        Vec::new()
+    }
     async fn on_start(&self) {}
     async fn on_shutdown(&self) {}
 }
@@ -22,7 +33,7 @@ pub async fn build_app(
     plugins: &Vec<Box<dyn Plugin>>,
     metrics: Option<MaybeMetrics>,
     cache: Option<DynCache>,
-    api_key: Option<String>,
+    auth: Option<AuthPolicy>,
 ) -> Router {
     let mut app = Router::new();
 
@@ -64,34 +75,42 @@ pub async fn build_app(
             ));
         }
 
-        let api_key_for_mw = api_key.clone();
-        let plugin_name = plugin.name().to_string();
-        router = router.layer(axum::middleware::from_fn(move |req: Request<Body>, next: Next| {
-            let api_key_for_mw = api_key_for_mw.clone();
-            let plugin_name = plugin_name.clone();
-            async move {
-                if api_key_for_mw.is_some() && plugin_name != "health" {
-                    if req.method() != Method::OPTIONS {
-                        if plugin_name == "auth" && req.method() == Method::POST && req.uri().path().contains("/login") {
+        if let Some(auth) = auth.clone() {
+            let rules = std::sync::Arc::new(plugin.access_rules());
+            let default = plugin.default_access();
+            let name = plugin.name();
+            let prefix = format!("/{}", name);
+            router = router.layer(axum::middleware::from_fn(
+                move |mut req: Request<Body>, next: Next| {
+                    let auth = auth.clone();
+                    let rules = rules.clone();
+                    let prefix = prefix.clone();
+                    async move {
+                        if req.method() == Method::OPTIONS {
                             return next.run(req).await;
                         }
-                        let auth_present = req.headers().get("authorization").is_some();
-                        let header_val = req
-                            .headers()
-                            .get("x-api-key")
-                            .and_then(|v| v.to_str().ok())
-                            .map(|s| s.to_string());
-                        let api_ok = header_val.as_deref() == api_key_for_mw.as_deref();
-                        if !api_ok && !auth_present {
-                            let mut res = Response::new(Body::empty());
-                            *res.status_mut() = StatusCode::UNAUTHORIZED;
-                            return res;
+                        let access = match req.extensions().get::<axum::extract::MatchedPath>() {
+                            Some(matched) => {
+                                let route = matched.as_str();
+                                let route = route.strip_prefix(prefix.as_str()).unwrap_or(route);
+                                let route = if route.is_empty() { "/" } else { route };
+                                policy::resolve(&rules, default, req.method(), route)
+                            }
+                            None => default,
+                        };
+                        let scope = crate::plugins::auth::api_keys::request_scope(name, req.method());
+                        match auth.authorize(access, &scope, req.headers()).await {
+                            Ok(Some(user)) => {
+                                req.extensions_mut().insert(user);
+                                next.run(req).await
+                            }
+                            Ok(None) => next.run(req).await,
+                            Err(e) => e.into_response(),
                         }
                     }
-                }
-                next.run(req).await
-            }
-        }));
+                },
+            ));
+        }
 
         router = router.layer(axum::middleware::from_fn(|req: Request<Body>, next: Next| async move {
             let allowed_env = std::env::var("ALLOWED_ORIGINS").ok();
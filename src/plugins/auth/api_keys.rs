@@ -0,0 +1,145 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::models::{ApiKeyCreated, ApiKeyDto};
+use crate::plugins::auth::{rbac, repo};
+use axum::http::StatusCode;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "eco_";
+/// Characters of a key kept in `api_keys.prefix`.
+const SHOWN_CHARS: usize = 12;
+/// `last_used_at` is only rewritten once it is at least this old.
+const TOUCH_EVERY_SECS: i64 = 60;
+
+pub fn hash_key(key: &str) -> Vec<u8> {
+    Sha256::digest(key.as_bytes()).to_vec()
+}
+
+/// `true` if `granted` covers `needed`, a `resource:action` scope. `*`
+/// grants everything and `resource:*` every action on the resource.
+pub fn scope_allows(granted: &[String], needed: &str) -> bool {
+    let resource = needed.split(':').next().unwrap_or(needed);
+    granted
+        .iter()
+        .any(|g| g == "*" || g == needed || g.strip_suffix(":*") == Some(resource))
+}
+
+/// The scope a request needs: the last segment of the plugin name and
+/// `read` for `GET`/`HEAD`, `write` otherwise, e.g. `blog:read`.
+pub fn request_scope(plugin_name: &str, method: &axum::http::Method) -> String {
+    let resource = plugin_name.rsplit('/').next().unwrap_or(plugin_name);
+    let action = match *method {
+        axum::http::Method::GET | axum::http::Method::HEAD => "read",
+        _ => "write",
+    };
+    format!("{}:{}", resource, action)
+}
+
+/// Accepts `*`, `resource:read|write|*` and the permissions in
+/// [`rbac::PERMISSIONS`] an API key can hold, such as `tangle:operate`.
+fn check_scopes(scopes: &[String]) -> Result<(), AppError> {
+    let valid = |s: &str| {
+        if s == "*" {
+            return true;
+        }
+        if rbac::PERMISSIONS.contains(&s) {
+            return !rbac::BEARER_ONLY.contains(&s);
+        }
+        let Some((resource, action)) = s.split_once(':') else {
+            return false;
+        };
+        !resource.is_empty()
+            && resource
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            && matches!(action, "read" | "write" | "*")
+    };
+    if scopes.is_empty() {
+        return Err(
+            AppError::new(StatusCode::BAD_REQUEST, "scopesRequired").with_code("invalid_scope")
+        );
+    }
+    match scopes.iter().find(|s| !valid(s)) {
+        Some(bad) => Err(
+            AppError::new(StatusCode::BAD_REQUEST, format!("invalidScope: {}", bad))
+                .with_code("invalid_scope"),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Generates a key and stores its hash.
+pub async fn issue(
+    pool: &PgPool,
+    name: &str,
+    owner_id: Uuid,
+    scopes: &[String],
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<ApiKeyCreated, AppError> {
+    if name.trim().is_empty() {
+        return Err(
+            AppError::new(StatusCode::BAD_REQUEST, "nameRequired").with_code("invalid_name")
+        );
+    }
+    check_scopes(scopes)?;
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let key = format!("{}{}", KEY_PREFIX, URL_SAFE_NO_PAD.encode(secret));
+    let api_key = repo::insert_api_key(
+        pool,
+        name.trim(),
+        owner_id,
+        &key[..SHOWN_CHARS],
+        &hash_key(&key),
+        scopes,
+        expires_at,
+    )
+    .await?;
+    Ok(ApiKeyCreated { key, api_key })
+}
+
+/// Issues a replacement for key `id` with the same name, owner, scopes and
+/// expiry; the old key stops working after `grace_secs`.
+pub async fn rotate(pool: &PgPool, id: Uuid, grace_secs: i64) -> Result<ApiKeyCreated, AppError> {
+    let old = repo::get_api_key(pool, id)
+        .await?
+        .filter(|k| k.revoked_at.is_none())
+        .ok_or_else(api_key_not_found)?;
+    let created = issue(pool, &old.name, old.owner_id, &old.scopes, old.expires_at).await?;
+    if grace_secs > 0 {
+        repo::expire_api_key(pool, id, grace_secs).await?;
+    } else {
+        repo::revoke_api_key(pool, id).await?;
+    }
+    Ok(created)
+}
+
+/// The active key `presented` stands for, if any.
+pub async fn authenticate(pool: &PgPool, presented: &str) -> Result<Option<ApiKeyDto>, AppError> {
+    if !presented.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+    let Some(key) = repo::find_active_api_key(pool, &hash_key(presented)).await? else {
+        return Ok(None);
+    };
+    let stale = key
+        .last_used_at
+        .is_none_or(|at| chrono::Utc::now() - at >= chrono::Duration::seconds(TOUCH_EVERY_SECS));
+    if stale {
+        let (pool, id) = (pool.clone(), key.id);
+        tokio::spawn(async move {
+            if let Err(e) = repo::touch_api_key(&pool, id, TOUCH_EVERY_SECS).await {
+                tracing::warn!("could not record use of api key {}: {}", id, e.message);
+            }
+        });
+    }
+    Ok(Some(key))
+}
+
+pub fn api_key_not_found() -> AppError {
+    AppError::new(StatusCode::NOT_FOUND, "apiKeyNotFound").with_code("not_found")
+}
@@ -1,27 +1,31 @@
+use crate::cache::DynCache;
 use crate::http_error::AppError;
-use crate::plugins::auth::models::{LoginRequest, LoginResponse};
-use crate::plugins::auth::repo;
+use crate::plugins::auth::models::{
+    ApiKeyCreate, ApiKeyCreated, ApiKeyDto, ApiKeyRotate, LoginFailureDto, LoginFailuresQuery,
+    LoginOutcome, LoginRequest, LoginResponse, MfaChallenge, MfaCodeRequest, MfaLoginRequest,
+    MfaStatus, RecoveryCodes, RefreshRequest, TotpEnrollment,
+};
+use crate::plugins::auth::throttle::LoginThrottle;
+use crate::plugins::auth::{api_keys, mfa, repo, tokens};
 use crate::plugins::users::models::UserDto;
 use async_trait::async_trait;
-use axum::extract::FromRequestParts;
-use axum::http::StatusCode;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::extract::{Path, Query};
 use axum::http::request::Parts;
-use axum::{Json, extract::State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::{Extension, Json, extract::State};
 use bcrypt::verify;
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
-use serde::Serialize;
 use sqlx::PgPool;
-use std::env;
-
-#[derive(Serialize, serde::Deserialize)]
-struct Claims {
-    sub: String,
-    exp: usize,
-}
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock};
 
 #[derive(Clone)]
 pub struct AuthUser {
     pub user_id: uuid::Uuid,
+    /// From the token, see [`crate::plugins::auth::rbac`].
+    pub roles: Vec<String>,
+    /// Whether the session was started with a second factor.
+    pub mfa: bool,
 }
 
 #[async_trait]
@@ -32,45 +36,48 @@ where
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let auth_hdr = parts
-            .headers
-            .get("authorization")
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| {
-                AppError::new(StatusCode::UNAUTHORIZED, "missing authorization")
-                    .with_code("missing_token")
-            })?;
-
-        if !auth_hdr.starts_with("Bearer ") {
-            return Err(
-                AppError::new(StatusCode::UNAUTHORIZED, "invalid authorization header")
-                    .with_code("invalid_token"),
-            );
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
         }
-        let token = &auth_hdr[7..];
-        let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string());
-        let validation = Validation::default();
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(secret.as_bytes()),
-            &validation,
-        )
-        .map_err(|_| {
-            AppError::new(StatusCode::UNAUTHORIZED, "invalid token").with_code("invalid_token")
-        })?;
-        let sub = token_data.claims.sub;
-        let user_id = uuid::Uuid::parse_str(&sub).map_err(|_| {
-            AppError::new(StatusCode::UNAUTHORIZED, "invalid token subject")
-                .with_code("invalid_token")
+        let pool = parts.extensions.get::<PgPool>().ok_or_else(|| {
+            tracing::error!("AuthUser used on a router without Extension<PgPool>");
+            AppError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "revocationListUnavailable",
+            )
         })?;
-        Ok(AuthUser { user_id })
+        bearer_user(&parts.headers, pool).await
     }
 }
 
+/// Validates the `Authorization: Bearer` token in `headers`, see
+/// [`tokens::bearer_claims`].
+pub async fn bearer_user(headers: &HeaderMap, pool: &PgPool) -> Result<AuthUser, AppError> {
+    let claims = tokens::bearer_claims(headers, pool).await?;
+    let user_id = uuid::Uuid::parse_str(&claims.sub).map_err(|_| {
+        AppError::new(StatusCode::UNAUTHORIZED, "invalid token subject").with_code("invalid_token")
+    })?;
+    Ok(AuthUser {
+        user_id,
+        roles: claims.roles,
+        mfa: claims.mfa,
+    })
+}
+
+/// Hash checked for unknown usernames, so they take as long to reject as a
+/// wrong password.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    bcrypt::hash("ecoblock-dummy-password", bcrypt::DEFAULT_COST).expect("bcrypt dummy hash")
+});
+
 pub async fn login(
     State(pool): State<PgPool>,
+    Extension(cache): Extension<Option<DynCache>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, AppError> {
+) -> Result<Json<LoginOutcome>, AppError> {
     if payload.username.is_empty() || payload.password.is_empty() {
         return Err(
             AppError::new(StatusCode::BAD_REQUEST, "usernameAndPasswordRequired")
@@ -78,43 +85,128 @@ pub async fn login(
         );
     }
 
+    let cache = cache.as_ref();
+    let ip = throttle.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
     let user = repo::find_user_by_username(&pool, &payload.username).await?;
-    let (id, password_hash) = match user {
-        Some((id, hash)) => (id, hash),
-        None => {
-            return Err(
-                AppError::new(StatusCode::UNAUTHORIZED, "invalidUsernameOrPassword")
-                    .with_code("invalid_credentials"),
-            );
-        }
-    };
+    let user_id = user.as_ref().map(|(id, _)| *id);
+    if let Err(e) = throttle.check(cache, &payload.username, ip).await {
+        audit_login_failure(&pool, &payload.username, user_id, ip, "locked").await;
+        return Err(e);
+    }
 
-    let valid = verify(&payload.password, &password_hash)
+    let password_hash = user
+        .as_ref()
+        .map_or(DUMMY_HASH.as_str(), |(_, hash)| hash.as_str());
+    let valid = verify(&payload.password, password_hash)
         .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-    if !valid {
+    let Some(id) = user_id.filter(|_| valid) else {
+        let reason = if user_id.is_some() {
+            "bad_password"
+        } else {
+            "unknown_user"
+        };
+        throttle.record_failure(cache, &payload.username, ip).await;
+        audit_login_failure(&pool, &payload.username, user_id, ip, reason).await;
         return Err(
             AppError::new(StatusCode::UNAUTHORIZED, "invalidUsernameOrPassword")
                 .with_code("invalid_credentials"),
         );
+    };
+
+    throttle.record_success(cache, &payload.username).await;
+    if mfa::is_enabled(&pool, id).await? {
+        return Ok(Json(LoginOutcome::MfaRequired(MfaChallenge {
+            mfa_required: true,
+            mfa_token: tokens::service()?.issue_challenge(id)?,
+            expires_in: tokens::CHALLENGE_TTL.as_secs(),
+        })));
     }
+    Ok(Json(LoginOutcome::Session(
+        tokens::issue_session(&pool, id, false).await?,
+    )))
+}
 
-    let secret = env::var("JWT_SECRET").map_err(|_| {
-        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "jwtSecretNotConfigured")
-            .with_code("config_error")
-    })?;
-    let exp = (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize;
-    let claims = Claims {
-        sub: id.to_string(),
-        exp,
-    };
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
-    .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// Second step of a login for users with a second factor. Failed codes are
+/// throttled and audited like failed passwords.
+pub async fn login_mfa(
+    State(pool): State<PgPool>,
+    Extension(cache): Extension<Option<DynCache>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<MfaLoginRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let user_id = tokens::service()?.verify_challenge(&payload.mfa_token)?;
+    let ip = throttle.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
+    check_mfa_code(&pool, cache.as_ref(), &throttle, ip, user_id, &payload.code).await?;
+    Ok(Json(tokens::issue_session(&pool, user_id, true).await?))
+}
+
+/// Uses up a second-factor code of `user_id`, throttled per user like
+/// passwords are; failures are audited like failed logins.
+async fn check_mfa_code(
+    pool: &PgPool,
+    cache: Option<&DynCache>,
+    throttle: &LoginThrottle,
+    ip: Option<IpAddr>,
+    user_id: uuid::Uuid,
+    code: &str,
+) -> Result<(), AppError> {
+    let throttle_key = format!("mfa:{}", user_id);
+    let (_, username, _) = repo::get_user_basic(pool, user_id).await?;
+    if let Err(e) = throttle.check(cache, &throttle_key, ip).await {
+        audit_login_failure(pool, &username, Some(user_id), ip, "locked").await;
+        return Err(e);
+    }
+    if !mfa::verify(pool, user_id, code).await? {
+        throttle.record_failure(cache, &throttle_key, ip).await;
+        audit_login_failure(pool, &username, Some(user_id), ip, "bad_mfa_code").await;
+        return Err(mfa::invalid_code());
+    }
+    throttle.record_success(cache, &throttle_key).await;
+    Ok(())
+}
+
+/// A failed login that cannot be recorded is logged rather than failing
+/// the request.
+async fn audit_login_failure(
+    pool: &PgPool,
+    username: &str,
+    user_id: Option<uuid::Uuid>,
+    ip: Option<IpAddr>,
+    reason: &str,
+) {
+    tracing::info!(username, ?ip, reason, "login failed");
+    let ip = ip.map(|ip| ip.to_string());
+    if let Err(e) = repo::insert_login_failure(pool, username, user_id, ip, reason).await {
+        tracing::error!("recording login failure: {}", e.message);
+    }
+}
+
+pub async fn list_login_failures(
+    State(pool): State<PgPool>,
+    Query(q): Query<LoginFailuresQuery>,
+) -> Result<Json<Vec<LoginFailureDto>>, AppError> {
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    Ok(Json(
+        repo::list_login_failures(&pool, q.username.as_deref(), limit).await?,
+    ))
+}
 
-    Ok(Json(LoginResponse { token }))
+pub async fn refresh(
+    State(pool): State<PgPool>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    Ok(Json(tokens::refresh(&pool, &payload.refresh_token).await?))
+}
+
+pub async fn logout(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let claims = tokens::bearer_claims(&headers, &pool).await?;
+    tokens::logout(&pool, &claims).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn whoami(State(pool): State<PgPool>, auth: AuthUser) -> Result<Json<UserDto>, AppError> {
@@ -125,3 +217,116 @@ pub async fn whoami(State(pool): State<PgPool>, auth: AuthUser) -> Result<Json<U
         email,
     }))
 }
+
+pub async fn create_api_key(
+    State(pool): State<PgPool>,
+    auth: AuthUser,
+    Json(payload): Json<ApiKeyCreate>,
+) -> Result<(StatusCode, Json<ApiKeyCreated>), AppError> {
+    let owner_id = payload.owner_id.unwrap_or(auth.user_id);
+    let created = api_keys::issue(
+        &pool,
+        &payload.name,
+        owner_id,
+        &payload.scopes,
+        payload.expires_at,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+pub async fn list_api_keys(State(pool): State<PgPool>) -> Result<Json<Vec<ApiKeyDto>>, AppError> {
+    Ok(Json(repo::list_api_keys(&pool).await?))
+}
+
+pub async fn revoke_api_key(
+    State(pool): State<PgPool>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<StatusCode, AppError> {
+    if !repo::revoke_api_key(&pool, id).await? {
+        return Err(api_keys::api_key_not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn rotate_api_key(
+    State(pool): State<PgPool>,
+    Path(id): Path<uuid::Uuid>,
+    Query(q): Query<ApiKeyRotate>,
+) -> Result<(StatusCode, Json<ApiKeyCreated>), AppError> {
+    let created = api_keys::rotate(&pool, id, q.grace_secs.max(0)).await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+pub async fn mfa_status(
+    State(pool): State<PgPool>,
+    auth: AuthUser,
+) -> Result<Json<MfaStatus>, AppError> {
+    Ok(Json(mfa::status(&pool, auth.user_id).await?))
+}
+
+pub async fn enroll_totp(
+    State(pool): State<PgPool>,
+    auth: AuthUser,
+) -> Result<(StatusCode, Json<TotpEnrollment>), AppError> {
+    let enrollment = mfa::enroll(&pool, auth.user_id).await?;
+    Ok((StatusCode::CREATED, Json(enrollment)))
+}
+
+pub async fn confirm_totp(
+    State(pool): State<PgPool>,
+    auth: AuthUser,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<Json<RecoveryCodes>, AppError> {
+    let codes = mfa::confirm(&pool, auth.user_id, &payload.code).await?;
+    tracing::info!(user_id = %auth.user_id, "two-factor authentication enabled");
+    Ok(Json(codes))
+}
+
+pub async fn disable_totp(
+    State(pool): State<PgPool>,
+    Extension(cache): Extension<Option<DynCache>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    auth: AuthUser,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<StatusCode, AppError> {
+    let ip = throttle.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
+    check_mfa_code(
+        &pool,
+        cache.as_ref(),
+        &throttle,
+        ip,
+        auth.user_id,
+        &payload.code,
+    )
+    .await?;
+    mfa::disable(&pool, auth.user_id).await?;
+    tracing::info!(user_id = %auth.user_id, "two-factor authentication disabled");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn regenerate_recovery_codes(
+    State(pool): State<PgPool>,
+    Extension(cache): Extension<Option<DynCache>>,
+    Extension(throttle): Extension<Arc<LoginThrottle>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    auth: AuthUser,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<Json<RecoveryCodes>, AppError> {
+    let ip = throttle.client_ip(&headers, peer.map(|ConnectInfo(addr)| addr));
+    check_mfa_code(
+        &pool,
+        cache.as_ref(),
+        &throttle,
+        ip,
+        auth.user_id,
+        &payload.code,
+    )
+    .await?;
+    Ok(Json(
+        mfa::regenerate_recovery_codes(&pool, auth.user_id).await?,
+    ))
+}
@@ -0,0 +1,208 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::models::{MfaStatus, RecoveryCodes, TotpEnrollment};
+use crate::plugins::auth::repo;
+use axum::http::StatusCode;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Shown as the account's issuer in authenticator apps.
+pub const ISSUER: &str = "ecoblock";
+const DIGITS: u32 = 6;
+const PERIOD_SECS: u64 = 30;
+/// Codes of the steps this far before or after the current one are
+/// accepted, for clock drift.
+const SKEW_STEPS: u64 = 1;
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// The RFC 6238 code of `secret` for time step `step` (HMAC-SHA1, six
+/// digits).
+pub fn totp(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        hash[offset],
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]) & 0x7fff_ffff;
+    bin % 10u32.pow(DIGITS)
+}
+
+/// The step `code` is valid for at unix time `now`, if any step within
+/// [`SKEW_STEPS`] and after `last_used_step` matches.
+pub fn matching_step(
+    secret: &[u8],
+    code: &str,
+    now: u64,
+    last_used_step: Option<i64>,
+) -> Option<u64> {
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = now / PERIOD_SECS;
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+        .find(|step| totp(secret, *step) == code)
+}
+
+/// RFC 4648 base32 without padding, as authenticator apps expect secrets.
+pub fn base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+/// The `otpauth://` URI authenticator apps scan from a QR code.
+pub fn otpauth_uri(account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD_SECS}",
+        issuer = percent_encode(ISSUER),
+        account = percent_encode(account),
+        secret = base32(secret),
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Starts enrollment with a new secret, replacing one not yet confirmed.
+pub async fn enroll(pool: &PgPool, user_id: Uuid) -> Result<TotpEnrollment, AppError> {
+    let mut secret = [0u8; SECRET_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    if !repo::upsert_pending_totp(pool, user_id, &secret).await? {
+        return Err(
+            AppError::new(StatusCode::CONFLICT, "mfaAlreadyEnabled").with_code("mfa_enabled")
+        );
+    }
+    let (_, username, _) = repo::get_user_basic(pool, user_id).await?;
+    Ok(TotpEnrollment {
+        secret: base32(&secret),
+        otpauth_uri: otpauth_uri(&username, &secret),
+    })
+}
+
+/// Enables the pending secret once `code` shows the app has it, and hands
+/// out the first recovery codes.
+pub async fn confirm(pool: &PgPool, user_id: Uuid, code: &str) -> Result<RecoveryCodes, AppError> {
+    let totp = repo::find_totp(pool, user_id)
+        .await?
+        .filter(|t| t.enabled_at.is_none())
+        .ok_or_else(|| {
+            AppError::new(StatusCode::CONFLICT, "mfaNotPending").with_code("mfa_not_pending")
+        })?;
+    let Some(step) = matching_step(&totp.secret, code, now_secs(), totp.last_used_step) else {
+        return Err(invalid_code());
+    };
+    let codes = new_recovery_codes();
+    let hashes: Vec<Vec<u8>> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+    repo::enable_totp(pool, user_id, step as i64, &hashes).await?;
+    Ok(RecoveryCodes {
+        recovery_codes: codes,
+    })
+}
+
+/// Whether `user_id` has a confirmed second factor.
+pub async fn is_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, AppError> {
+    Ok(repo::find_totp(pool, user_id)
+        .await?
+        .is_some_and(|t| t.enabled_at.is_some()))
+}
+
+/// Checks a TOTP code, or else a recovery code, of an enabled second factor
+/// and uses it up.
+pub async fn verify(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, AppError> {
+    let Some(totp) = repo::find_totp(pool, user_id)
+        .await?
+        .filter(|t| t.enabled_at.is_some())
+    else {
+        return Ok(false);
+    };
+    let code = code.trim();
+    if let Some(step) = matching_step(&totp.secret, code, now_secs(), totp.last_used_step) {
+        return repo::use_totp_step(pool, user_id, step as i64).await;
+    }
+    repo::use_recovery_code(pool, user_id, &hash_recovery_code(code)).await
+}
+
+/// Turns the second factor off; the caller has just [`verify`]-ed a code.
+pub async fn disable(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    repo::delete_totp(pool, user_id).await
+}
+
+/// Replaces the recovery codes; the caller has just [`verify`]-ed a code.
+pub async fn regenerate_recovery_codes(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<RecoveryCodes, AppError> {
+    let codes = new_recovery_codes();
+    let hashes: Vec<Vec<u8>> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+    repo::replace_recovery_codes(pool, user_id, &hashes).await?;
+    Ok(RecoveryCodes {
+        recovery_codes: codes,
+    })
+}
+
+pub async fn status(pool: &PgPool, user_id: Uuid) -> Result<MfaStatus, AppError> {
+    Ok(MfaStatus {
+        enabled: is_enabled(pool, user_id).await?,
+        recovery_codes_remaining: repo::count_recovery_codes(pool, user_id).await?,
+    })
+}
+
+pub fn invalid_code() -> AppError {
+    AppError::new(StatusCode::UNAUTHORIZED, "invalidMfaCode").with_code("invalid_mfa_code")
+}
+
+/// `xxxxx-xxxxx`, lowercase base32.
+fn new_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let code = base32(&bytes).to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..10])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without case, dashes or spaces.
+fn hash_recovery_code(code: &str) -> Vec<u8> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Sha256::digest(normalized.as_bytes()).to_vec()
+}
+
+fn now_secs() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
@@ -0,0 +1,54 @@
+use crate::plugins::auth::mfa::{base32, matching_step, otpauth_uri, totp};
+
+// RFC 6238 appendix B, SHA-1, truncated to six digits.
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+#[test]
+fn totp_matches_rfc_6238_vectors() {
+    for (time, code) in [
+        (59u64, 287082u32),
+        (1111111109, 81804),
+        (1111111111, 50471),
+        (1234567890, 5924),
+        (2000000000, 279037),
+        (20000000000, 353130),
+    ] {
+        assert_eq!(totp(RFC_SECRET, time / 30), code, "t={}", time);
+    }
+}
+
+#[test]
+fn matching_step_allows_one_step_of_drift_and_no_replay() {
+    let now = 1111111109;
+    let step = now / 30;
+    let code = |s: u64| format!("{:06}", totp(RFC_SECRET, s));
+    assert_eq!(
+        matching_step(RFC_SECRET, &code(step), now, None),
+        Some(step)
+    );
+    assert_eq!(
+        matching_step(RFC_SECRET, &code(step - 1), now, None),
+        Some(step - 1)
+    );
+    assert_eq!(
+        matching_step(RFC_SECRET, &code(step + 1), now, None),
+        Some(step + 1)
+    );
+    assert_eq!(matching_step(RFC_SECRET, &code(step - 2), now, None), None);
+    assert_eq!(
+        matching_step(RFC_SECRET, &code(step), now, Some(step as i64)),
+        None
+    );
+    assert_eq!(matching_step(RFC_SECRET, "12345", now, None), None);
+    assert_eq!(matching_step(RFC_SECRET, "abcdef", now, None), None);
+}
+
+#[test]
+fn otpauth_uri_encodes_the_secret_in_base32() {
+    assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+    assert_eq!(base32(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(
+        otpauth_uri("ann lee", b"foobar"),
+        "otpauth://totp/ecoblock:ann%20lee?secret=MZXW6YTBOI&issuer=ecoblock&algorithm=SHA1&digits=6&period=30"
+    );
+}
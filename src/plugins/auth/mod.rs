@@ -1,7 +1,15 @@
+pub mod api_keys;
 pub mod handlers;
-pub mod middleware;
+pub mod mfa;
 pub mod models;
 pub mod plugin;
+pub mod policy;
+pub mod rbac;
 pub mod repo;
+pub mod throttle;
+pub mod tokens;
 
 pub use plugin::AuthPlugin;
+
+#[cfg(test)]
+mod mfa_tests;
@@ -8,5 +8,139 @@ pub struct LoginRequest {
 
 #[derive(Serialize)]
 pub struct LoginResponse {
+    /// Access token.
     pub token: String,
+    pub refresh_token: String,
+    pub token_type: &'static str,
+    /// Seconds until `token` expires.
+    pub expires_in: u64,
+}
+
+/// Returned by login instead of a session when the user has a second
+/// factor; `mfa_token` and a code go to `/auth/login/mfa`.
+#[derive(Serialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    /// Seconds until `mfa_token` expires.
+    pub expires_in: u64,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginOutcome {
+    Session(LoginResponse),
+    MfaRequired(MfaChallenge),
+}
+
+#[derive(Deserialize)]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    /// A TOTP code or a recovery code.
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct MfaCodeRequest {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollment {
+    /// Base32, for entering by hand.
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Shown once; only hashes are stored.
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct TotpRow {
+    pub secret: Vec<u8>,
+    pub enabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_step: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct LoginFailuresQuery {
+    pub username: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct LoginFailureDto {
+    pub id: i64,
+    pub username: String,
+    /// Set when the username exists.
+    pub user_id: Option<uuid::Uuid>,
+    pub ip: Option<String>,
+    /// `bad_password`, `unknown_user`, `locked` or `bad_mfa_code`.
+    pub reason: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct RefreshTokenRow {
+    pub id: uuid::Uuid,
+    pub family_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub mfa: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ApiKeyCreate {
+    pub name: String,
+    /// E.g. `tangle:write`, `blog:read`, `tangle:*` or `*`.
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Defaults to the admin creating the key.
+    pub owner_id: Option<uuid::Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct ApiKeyRotate {
+    /// Seconds the replaced key keeps working; 0 revokes it at once.
+    #[serde(default)]
+    pub grace_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ApiKeyDto {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub owner_id: uuid::Uuid,
+    /// First characters of the key, enough to tell keys apart.
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Returned once when a key is created or rotated; the key itself is not
+/// stored and cannot be shown again.
+#[derive(Serialize)]
+pub struct ApiKeyCreated {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyDto,
 }
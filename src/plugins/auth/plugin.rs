@@ -1,45 +1,91 @@
 use crate::kernel::Plugin;
 use crate::plugins::auth::handlers;
+use crate::plugins::auth::policy::{Access, AccessRule};
+use crate::plugins::auth::throttle::LoginThrottle;
 use async_trait::async_trait;
+use axum::http::Method;
 use axum::{
-    Router, middleware,
-    routing::{get, post},
+    Extension, Router,
+    routing::{delete, get, post},
 };
 use sqlx::PgPool;
+use std::sync::Arc;
 
 pub struct AuthPlugin {
     pool: PgPool,
+    throttle: Arc<LoginThrottle>,
 }
 
 impl AuthPlugin {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            throttle: Arc::new(LoginThrottle::default()),
+        }
+    }
+
+    pub fn with_login_throttle(mut self, throttle: LoginThrottle) -> Self {
+        self.throttle = Arc::new(throttle);
+        self
     }
 }
 
 #[async_trait]
 impl Plugin for AuthPlugin {
     async fn router(&self) -> Router {
-        let pool1 = self.pool.clone();
         let pool2 = self.pool.clone();
-        let public = Router::new().route(
-            "/login",
-            post(move |payload| handlers::login(axum::extract::State(pool1.clone()), payload)),
-        );
-
-        let protected = Router::new()
+        Router::new()
             .route(
                 "/whoami",
                 get(move |auth| handlers::whoami(axum::extract::State(pool2.clone()), auth)),
             )
-            .layer(middleware::from_fn(
-                crate::plugins::auth::middleware::require_auth,
-            ));
-
-        public.merge(protected)
+            .merge(
+                Router::new()
+                    .route("/login", post(handlers::login))
+                    .route("/login/mfa", post(handlers::login_mfa))
+                    .route("/login-failures", get(handlers::list_login_failures))
+                    .route("/refresh", post(handlers::refresh))
+                    .route("/logout", post(handlers::logout))
+                    .route("/mfa", get(handlers::mfa_status))
+                    .route("/mfa/totp", post(handlers::enroll_totp))
+                    .route("/mfa/totp/confirm", post(handlers::confirm_totp))
+                    .route("/mfa/totp/disable", post(handlers::disable_totp))
+                    .route(
+                        "/mfa/recovery-codes",
+                        post(handlers::regenerate_recovery_codes),
+                    )
+                    .route(
+                        "/api-keys",
+                        post(handlers::create_api_key).get(handlers::list_api_keys),
+                    )
+                    .route("/api-keys/:id", delete(handlers::revoke_api_key))
+                    .route("/api-keys/:id/rotate", post(handlers::rotate_api_key))
+                    .layer(Extension(self.throttle.clone()))
+                    .with_state(self.pool.clone()),
+            )
+            .layer(Extension(self.pool.clone()))
     }
 
     fn name(&self) -> &'static str {
         "auth"
     }
+
+    fn access_rules(&self) -> Vec<AccessRule> {
+        vec![
+            AccessRule::on(Method::POST, "/login", Access::Public),
+            AccessRule::any("/login-failures", Access::Admin),
+            AccessRule::any("/whoami", Access::Bearer),
+            AccessRule::on(Method::POST, "/refresh", Access::Public),
+            AccessRule::on(Method::POST, "/login/mfa", Access::Public),
+            AccessRule::on(Method::POST, "/logout", Access::Bearer),
+            AccessRule::any("/mfa", Access::Bearer),
+            AccessRule::any("/mfa/totp", Access::Bearer),
+            AccessRule::any("/mfa/totp/confirm", Access::Bearer),
+            AccessRule::any("/mfa/totp/disable", Access::Bearer),
+            AccessRule::any("/mfa/recovery-codes", Access::Bearer),
+            AccessRule::any("/api-keys", Access::Admin),
+            AccessRule::any("/api-keys/:id", Access::Admin),
+            AccessRule::any("/api-keys/:id/rotate", Access::Admin),
+        ]
+    }
 }
@@ -0,0 +1,220 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::handlers::{AuthUser, bearer_user};
+use crate::plugins::auth::{api_keys, rbac};
+use axum::http::{HeaderMap, Method, StatusCode};
+use sqlx::PgPool;
+
+/// What a request must present to reach a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Anyone.
+    Public,
+    /// An `x-api-key` granting the route's scope (see
+    /// [`api_keys::request_scope`]), the kernel's own API key, or a valid
+    /// bearer token. Open unless an API key is required.
+    ApiKey,
+    /// A valid bearer token.
+    Bearer,
+    /// A valid bearer token of an admin user.
+    Admin,
+    /// A valid bearer token with a role granting the permission (see
+    /// [`crate::plugins::auth::rbac`]), or an `x-api-key` with the
+    /// permission as a scope unless it is [`rbac::BEARER_ONLY`].
+    Permission(&'static str),
+}
+
+/// Access for one route of a plugin, see [`crate::kernel::Plugin::access_rules`].
+#[derive(Debug, Clone)]
+pub struct AccessRule {
+    method: Option<Method>,
+    path: &'static str,
+    access: Access,
+}
+
+impl AccessRule {
+    /// Applies to every method of the route pattern `path`, e.g. `"/keys/:id"`.
+    pub fn any(path: &'static str, access: Access) -> Self {
+        Self {
+            method: None,
+            path,
+            access,
+        }
+    }
+
+    pub fn on(method: Method, path: &'static str, access: Access) -> Self {
+        Self {
+            method: Some(method),
+            path,
+            access,
+        }
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.path == path && self.method.as_ref().is_none_or(|m| m == method)
+    }
+}
+
+/// The access a plugin requires for a request, given the matched route
+/// pattern relative to the plugin's mount point.
+pub fn resolve(rules: &[AccessRule], default: Access, method: &Method, path: &str) -> Access {
+    rules
+        .iter()
+        .find(|r| r.matches(method, path))
+        .map_or(default, |r| r.access)
+}
+
+/// Enforces the [`Access`] plugins declare for their routes; passed to
+/// [`crate::kernel::build_app`].
+#[derive(Clone)]
+pub struct AuthPolicy {
+    pool: PgPool,
+    api_key: Option<String>,
+    require_api_key: bool,
+    require_admin_mfa: bool,
+}
+
+impl AuthPolicy {
+    /// `pool` is used to look up admin users and API keys.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            api_key: None,
+            require_api_key: false,
+            require_admin_mfa: false,
+        }
+    }
+
+    /// A key granting every scope, e.g. from the `API_KEY` env var. Setting
+    /// one also makes [`Access::ApiKey`] routes require a key.
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key.filter(|k| !k.is_empty());
+        self.require_api_key |= self.api_key.is_some();
+        self
+    }
+
+    /// Makes [`Access::ApiKey`] routes require a key or token even without
+    /// a kernel API key, i.e. with keys from `/auth/api-keys` only.
+    pub fn require_api_key(mut self, require: bool) -> Self {
+        self.require_api_key |= require;
+        self
+    }
+
+    /// Makes [`Access::Admin`] and [`Access::Permission`] routes reject
+    /// admin users whose session was not started with a second factor (see
+    /// [`crate::plugins::auth::mfa`]). Routes to enroll stay open to them.
+    pub fn require_admin_mfa(mut self, require: bool) -> Self {
+        self.require_admin_mfa = require;
+        self
+    }
+
+    /// Checks `headers` against `access`; `scope` is what an API key must
+    /// grant. Returns the authenticated user when a bearer token was
+    /// validated.
+    pub async fn authorize(
+        &self,
+        access: Access,
+        scope: &str,
+        headers: &HeaderMap,
+    ) -> Result<Option<AuthUser>, AppError> {
+        match access {
+            Access::Public => Ok(None),
+            Access::ApiKey => {
+                let presented = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+                if let Some(presented) = presented
+                    && self.api_key_grants(presented, scope).await?
+                {
+                    return Ok(None);
+                }
+                if headers.contains_key("authorization") {
+                    return bearer_user(headers, &self.pool).await.map(Some);
+                }
+                if presented.is_none() && !self.require_api_key {
+                    return Ok(None);
+                }
+                Err(AppError::new(
+                    StatusCode::UNAUTHORIZED,
+                    if presented.is_some() {
+                        "invalidApiKey"
+                    } else {
+                        "missingApiKey"
+                    },
+                )
+                .with_code("invalid_api_key"))
+            }
+            Access::Bearer => bearer_user(headers, &self.pool).await.map(Some),
+            Access::Admin => {
+                let user = bearer_user(headers, &self.pool).await?;
+                let is_admin =
+                    match crate::plugins::users::repo::is_admin(&self.pool, user.user_id).await {
+                        Ok(is_admin) => is_admin,
+                        // the token outlived its user
+                        Err(e) if e.status == StatusCode::NOT_FOUND => false,
+                        Err(e) => return Err(e),
+                    };
+                if !is_admin {
+                    return Err(AppError::new(StatusCode::FORBIDDEN, "adminRequired")
+                        .with_code("forbidden"));
+                }
+                if self.require_admin_mfa && !user.mfa {
+                    return Err(mfa_required());
+                }
+                Ok(Some(user))
+            }
+            Access::Permission(permission) => {
+                let presented = headers
+                    .get("x-api-key")
+                    .and_then(|v| v.to_str().ok())
+                    .filter(|_| !rbac::BEARER_ONLY.contains(&permission));
+                if let Some(presented) = presented
+                    && self.api_key_grants(presented, permission).await?
+                {
+                    return Ok(None);
+                }
+                if presented.is_some() && !headers.contains_key("authorization") {
+                    return Err(AppError::new(StatusCode::UNAUTHORIZED, "invalidApiKey")
+                        .with_code("invalid_api_key"));
+                }
+                let user = bearer_user(headers, &self.pool).await?;
+                rbac::check(&self.pool, &user, permission).await?;
+                if self.require_admin_mfa
+                    && !user.mfa
+                    && crate::plugins::users::repo::is_admin(&self.pool, user.user_id).await?
+                {
+                    return Err(mfa_required());
+                }
+                Ok(Some(user))
+            }
+        }
+    }
+
+    /// Whether `presented` is the kernel key or an active key granting
+    /// `scope`; a known key lacking the scope is an error.
+    async fn api_key_grants(&self, presented: &str, scope: &str) -> Result<bool, AppError> {
+        if self
+            .api_key
+            .as_deref()
+            .is_some_and(|k| constant_time_eq(presented.as_bytes(), k.as_bytes()))
+        {
+            return Ok(true);
+        }
+        let Some(key) = api_keys::authenticate(&self.pool, presented).await? else {
+            return Ok(false);
+        };
+        if !api_keys::scope_allows(&key.scopes, scope) {
+            return Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                format!("insufficientScope: {}", scope),
+            )
+            .with_code("insufficient_scope"));
+        }
+        Ok(true)
+    }
+}
+
+fn mfa_required() -> AppError {
+    AppError::new(StatusCode::FORBIDDEN, "mfaRequired").with_code("mfa_required")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
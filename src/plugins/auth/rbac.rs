@@ -0,0 +1,97 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::handlers::AuthUser;
+use crate::plugins::auth::repo;
+use async_trait::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use sqlx::PgPool;
+use std::marker::PhantomData;
+
+/// A permission a handler can require with [`RequirePermission`]; `NAME`
+/// is a row of the `permissions` table.
+pub trait Permission: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+macro_rules! permissions {
+    ($($(#[$doc:meta])* $ty:ident => $name:literal,)*) => {
+        $(
+            $(#[$doc])*
+            pub struct $ty;
+
+            impl Permission for $ty {
+                const NAME: &'static str = $name;
+            }
+        )*
+
+        /// Every permission declared below, in declaration order.
+        pub const PERMISSIONS: &[&str] = &[$($name),*];
+    };
+}
+
+permissions! {
+    /// Create admins and assign roles.
+    ManageUsers => "users:admin",
+    WriteBlog => "blog:write",
+    WriteStories => "stories:write",
+    /// Submitting blocks, alone or in batches.
+    WriteTangle => "tangle:write",
+    /// Checkpoints, imports and sync of the tangle.
+    OperateTangle => "tangle:operate",
+}
+
+/// Permissions only a signed-in user can exercise: an API key never grants
+/// them, whatever its scopes (`*` and `users:*` included).
+pub const BEARER_ONLY: &[&str] = &[ManageUsers::NAME];
+
+/// Extracts the bearer user if one of the roles in their token grants `P`.
+///
+/// For handlers that must stay guarded without an
+/// [`crate::plugins::auth::policy::AuthPolicy`]. Their routes should declare
+/// the same [`crate::plugins::auth::policy::Access::Permission`], and `P`
+/// should be in [`BEARER_ONLY`] so the policy does not let API keys through
+/// that the extractor then turns away.
+///
+/// The router must provide the pool as an `Extension<PgPool>`. Roles are
+/// read from the token, so a grant applies from the next login or refresh;
+/// what a role permits is looked up on each request.
+pub struct RequirePermission<P: Permission> {
+    pub user: AuthUser,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        let pool = parts.extensions.get::<PgPool>().ok_or_else(|| {
+            tracing::error!("RequirePermission used on a router without Extension<PgPool>");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "permissionsUnavailable")
+        })?;
+        check(pool, &user, P::NAME).await?;
+        Ok(Self {
+            user,
+            _permission: PhantomData,
+        })
+    }
+}
+
+/// Fails with 403 unless one of `user`'s roles grants `permission`.
+pub async fn check(pool: &PgPool, user: &AuthUser, permission: &str) -> Result<(), AppError> {
+    if !user.roles.is_empty() && repo::roles_grant_permission(pool, &user.roles, permission).await?
+    {
+        return Ok(());
+    }
+    Err(AppError::new(
+        StatusCode::FORBIDDEN,
+        format!("permissionRequired: {}", permission),
+    )
+    .with_code("forbidden"))
+}
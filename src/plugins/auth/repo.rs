@@ -1,4 +1,5 @@
 use crate::http_error::AppError;
+use crate::plugins::auth::models::{ApiKeyDto, LoginFailureDto, RefreshTokenRow, TotpRow};
 use sqlx::PgPool;
 use sqlx::Row;
 use uuid::Uuid;
@@ -30,3 +31,394 @@ pub async fn get_user_basic(pool: &PgPool, id: Uuid) -> Result<(Uuid, String, St
         .map_err(AppError::from)?;
     Ok((r.get("id"), r.get("username"), r.get("email")))
 }
+
+const API_KEY_COLUMNS: &str =
+    "id, name, owner_id, prefix, scopes, expires_at, last_used_at, created_at, revoked_at";
+
+pub async fn insert_api_key(
+    pool: &PgPool,
+    name: &str,
+    owner_id: Uuid,
+    prefix: &str,
+    key_hash: &[u8],
+    scopes: &[String],
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<ApiKeyDto, AppError> {
+    sqlx::query_as::<_, ApiKeyDto>(&format!(
+        "INSERT INTO public.api_keys (name, owner_id, prefix, key_hash, scopes, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        API_KEY_COLUMNS
+    ))
+    .bind(name)
+    .bind(owner_id)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(scopes)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn list_api_keys(pool: &PgPool) -> Result<Vec<ApiKeyDto>, AppError> {
+    sqlx::query_as::<_, ApiKeyDto>(&format!(
+        "SELECT {} FROM public.api_keys ORDER BY created_at DESC",
+        API_KEY_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn get_api_key(pool: &PgPool, id: Uuid) -> Result<Option<ApiKeyDto>, AppError> {
+    sqlx::query_as::<_, ApiKeyDto>(&format!(
+        "SELECT {} FROM public.api_keys WHERE id = $1",
+        API_KEY_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// The unrevoked, unexpired key with this hash.
+pub async fn find_active_api_key(
+    pool: &PgPool,
+    key_hash: &[u8],
+) -> Result<Option<ApiKeyDto>, AppError> {
+    sqlx::query_as::<_, ApiKeyDto>(&format!(
+        "SELECT {} FROM public.api_keys WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())",
+        API_KEY_COLUMNS
+    ))
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Records a use of the key unless one was recorded in the last `every_secs`.
+pub async fn touch_api_key(pool: &PgPool, id: Uuid, every_secs: i64) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE public.api_keys SET last_used_at = now() WHERE id = $1 AND (last_used_at IS NULL OR last_used_at <= now() - make_interval(secs => $2))",
+    )
+    .bind(id)
+    .bind(every_secs as f64)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Revokes an active key. Returns false when there is none with this id.
+pub async fn revoke_api_key(pool: &PgPool, id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE public.api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Lets an active key expire `grace_secs` from now, unless it expires sooner.
+pub async fn expire_api_key(pool: &PgPool, id: Uuid, grace_secs: i64) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE public.api_keys SET expires_at = LEAST(COALESCE(expires_at, 'infinity'), now() + make_interval(secs => $2)) WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(grace_secs as f64)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn insert_refresh_token(
+    pool: &PgPool,
+    family_id: Uuid,
+    user_id: Uuid,
+    token_hash: &[u8],
+    expires_at: chrono::DateTime<chrono::Utc>,
+    mfa: bool,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO public.refresh_tokens (family_id, user_id, token_hash, expires_at, mfa) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(family_id)
+    .bind(user_id)
+    .bind(token_hash)
+    .bind(expires_at)
+    .bind(mfa)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn find_refresh_token(
+    pool: &PgPool,
+    token_hash: &[u8],
+) -> Result<Option<RefreshTokenRow>, AppError> {
+    sqlx::query_as::<_, RefreshTokenRow>(
+        "SELECT id, family_id, user_id, expires_at, used_at, revoked_at, mfa FROM public.refresh_tokens WHERE token_hash = $1",
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Marks an unused, unrevoked token as exchanged. Returns false when a
+/// concurrent request got there first.
+pub async fn mark_refresh_token_used(pool: &PgPool, id: Uuid) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE public.refresh_tokens SET used_at = now() WHERE id = $1 AND used_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn revoke_refresh_family(pool: &PgPool, family_id: Uuid) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE public.refresh_tokens SET revoked_at = now() WHERE family_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(family_id)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Records a revoked access token and forgets those that have expired.
+pub async fn revoke_access_token(
+    pool: &PgPool,
+    jti: &str,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO public.revoked_access_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING",
+    )
+    .bind(jti)
+    .bind(expires_at)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    sqlx::query("DELETE FROM public.revoked_access_tokens WHERE expires_at < now()")
+        .execute(pool)
+        .await
+        .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Whether the access token `jti`, or the refresh token family it was issued
+/// from, has been revoked.
+pub async fn access_token_revoked(
+    pool: &PgPool,
+    jti: &str,
+    family_id: Option<Uuid>,
+) -> Result<bool, AppError> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM public.revoked_access_tokens WHERE jti = $1) OR EXISTS (SELECT 1 FROM public.refresh_tokens WHERE family_id = $2 AND revoked_at IS NOT NULL)",
+    )
+    .bind(jti)
+    .bind(family_id)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn roles_grant_permission(
+    pool: &PgPool,
+    roles: &[String],
+    permission: &str,
+) -> Result<bool, AppError> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM public.role_permissions WHERE role = ANY($1) AND permission = $2)",
+    )
+    .bind(roles)
+    .bind(permission)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn insert_login_failure(
+    pool: &PgPool,
+    username: &str,
+    user_id: Option<Uuid>,
+    ip: Option<String>,
+    reason: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO public.login_failures (username, user_id, ip, reason) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(username)
+    .bind(user_id)
+    .bind(ip)
+    .bind(reason)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn list_login_failures(
+    pool: &PgPool,
+    username: Option<&str>,
+    limit: i64,
+) -> Result<Vec<LoginFailureDto>, AppError> {
+    sqlx::query_as::<_, LoginFailureDto>(
+        "SELECT id, username, user_id, ip, reason, created_at FROM public.login_failures \
+         WHERE $1::text IS NULL OR username = $1 ORDER BY created_at DESC, id DESC LIMIT $2",
+    )
+    .bind(username)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Stores `secret` as the user's pending TOTP secret. Returns false when a
+/// confirmed one is in place.
+pub async fn upsert_pending_totp(
+    pool: &PgPool,
+    user_id: Uuid,
+    secret: &[u8],
+) -> Result<bool, AppError> {
+    let res = sqlx::query(
+        "INSERT INTO public.user_totp (user_id, secret) VALUES ($1, $2) \
+         ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = now() \
+         WHERE public.user_totp.enabled_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(secret)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn find_totp(pool: &PgPool, user_id: Uuid) -> Result<Option<TotpRow>, AppError> {
+    sqlx::query_as::<_, TotpRow>(
+        "SELECT secret, enabled_at, last_used_step FROM public.user_totp WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Records `step` as used. Returns false if it, or a later one, already was.
+pub async fn use_totp_step(pool: &PgPool, user_id: Uuid, step: i64) -> Result<bool, AppError> {
+    let res = sqlx::query(
+        "UPDATE public.user_totp SET last_used_step = $2 \
+         WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+    )
+    .bind(user_id)
+    .bind(step)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Confirms the pending secret, used at `step`, with fresh recovery codes.
+pub async fn enable_totp(
+    pool: &PgPool,
+    user_id: Uuid,
+    step: i64,
+    recovery_code_hashes: &[Vec<u8>],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    sqlx::query(
+        "UPDATE public.user_totp SET enabled_at = now(), last_used_step = $2 WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .bind(step)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::from)?;
+    insert_recovery_codes(&mut tx, user_id, recovery_code_hashes).await?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn delete_totp(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    sqlx::query("DELETE FROM public.user_totp WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+    sqlx::query("DELETE FROM public.recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn replace_recovery_codes(
+    pool: &PgPool,
+    user_id: Uuid,
+    hashes: &[Vec<u8>],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    insert_recovery_codes(&mut tx, user_id, hashes).await?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Replaces the user's recovery codes within `tx`.
+async fn insert_recovery_codes(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: Uuid,
+    hashes: &[Vec<u8>],
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM public.recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(AppError::from)?;
+    sqlx::query(
+        "INSERT INTO public.recovery_codes (user_id, code_hash) SELECT $1, unnest($2::bytea[])",
+    )
+    .bind(user_id)
+    .bind(hashes)
+    .execute(&mut **tx)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Marks an unused recovery code as used. Returns false if there is none.
+pub async fn use_recovery_code(
+    pool: &PgPool,
+    user_id: Uuid,
+    code_hash: &[u8],
+) -> Result<bool, AppError> {
+    let res = sqlx::query(
+        "UPDATE public.recovery_codes SET used_at = now() \
+         WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(code_hash)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn count_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM public.recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
@@ -0,0 +1,216 @@
+use crate::cache::DynCache;
+use crate::http_error::AppError;
+use axum::http::{HeaderMap, StatusCode};
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Failed-login tracking per username and per client IP, kept in the
+/// [`crate::cache::Cache`].
+///
+/// A key gets `free_attempts` failures; each one after that locks it for
+/// `base_delay`, doubled per further failure up to `max_lockout`. Counters
+/// are forgotten `window` after the last failure, and a successful login
+/// clears the username's counter. Without a cache, or when it fails, logins
+/// are not throttled.
+#[derive(Clone, Debug)]
+pub struct LoginThrottle {
+    free_attempts: u32,
+    ip_free_attempts: u32,
+    base_delay: Duration,
+    max_lockout: Duration,
+    window: Duration,
+    trust_forwarded_for: bool,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self {
+            free_attempts: 5,
+            ip_free_attempts: 20,
+            base_delay: Duration::from_secs(1),
+            max_lockout: Duration::from_secs(15 * 60),
+            window: Duration::from_secs(15 * 60),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl LoginThrottle {
+    /// Defaults overridden by `LOGIN_FREE_ATTEMPTS`, `LOGIN_IP_FREE_ATTEMPTS`,
+    /// `LOGIN_MAX_LOCKOUT_SECS`, `LOGIN_WINDOW_SECS` and
+    /// `LOGIN_TRUST_FORWARDED_FOR`.
+    pub fn from_env() -> Self {
+        let number = |name: &str| env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        Self::default()
+            .with_free_attempts(
+                number("LOGIN_FREE_ATTEMPTS").map_or(defaults.free_attempts, |n| n as u32),
+                number("LOGIN_IP_FREE_ATTEMPTS").map_or(defaults.ip_free_attempts, |n| n as u32),
+            )
+            .with_delays(
+                defaults.base_delay,
+                number("LOGIN_MAX_LOCKOUT_SECS").map_or(defaults.max_lockout, Duration::from_secs),
+            )
+            .with_window(number("LOGIN_WINDOW_SECS").map_or(defaults.window, Duration::from_secs))
+            .trust_forwarded_for(
+                env::var("LOGIN_TRUST_FORWARDED_FOR").is_ok_and(|v| v == "true" || v == "1"),
+            )
+    }
+
+    /// Failures allowed before the first lockout, per username and per IP.
+    pub fn with_free_attempts(mut self, per_username: u32, per_ip: u32) -> Self {
+        self.free_attempts = per_username;
+        self.ip_free_attempts = per_ip;
+        self
+    }
+
+    pub fn with_delays(mut self, base_delay: Duration, max_lockout: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_lockout = max_lockout;
+        self
+    }
+
+    /// How long failures are remembered after the last one.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Take the client IP from the first `X-Forwarded-For` entry instead of
+    /// the peer address; only safe behind a proxy that sets the header.
+    pub fn trust_forwarded_for(mut self, trust: bool) -> Self {
+        self.trust_forwarded_for = trust;
+        self
+    }
+
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        if self.trust_forwarded_for
+            && let Some(ip) = headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok())
+        {
+            return Some(ip);
+        }
+        peer.map(|addr| addr.ip())
+    }
+
+    fn keys(&self, username: &str, ip: Option<IpAddr>) -> Vec<(String, u32)> {
+        let mut keys = vec![(
+            format!("auth:login:user:{}", username.to_lowercase()),
+            self.free_attempts,
+        )];
+        if let Some(ip) = ip {
+            keys.push((format!("auth:login:ip:{}", ip), self.ip_free_attempts));
+        }
+        keys
+    }
+
+    /// Fails with 429 while the username or the IP is locked out.
+    pub async fn check(
+        &self,
+        cache: Option<&DynCache>,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), AppError> {
+        let Some(cache) = cache else {
+            return Ok(());
+        };
+        let now = now_secs();
+        let mut locked_until = 0;
+        for (key, _) in self.keys(username, ip) {
+            if let Some(until) = load(cache, &lock_key(&key)).await {
+                locked_until = locked_until.max(until);
+            }
+        }
+        if locked_until > now {
+            return Err(
+                AppError::new(StatusCode::TOO_MANY_REQUESTS, "tooManyLoginAttempts")
+                    .with_code("too_many_attempts")
+                    .with_details(serde_json::json!({ "retry_after": locked_until - now })),
+            );
+        }
+        Ok(())
+    }
+
+    /// Counts a failed attempt against the username and the IP.
+    ///
+    /// Counters are bumped with [`crate::cache::Cache::incr`], so failures
+    /// arriving together all count towards the lockout. Attempts that were
+    /// already past [`Self::check`] when the lock was set still complete.
+    pub async fn record_failure(
+        &self,
+        cache: Option<&DynCache>,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) {
+        let Some(cache) = cache else {
+            return;
+        };
+        let now = now_secs();
+        for (key, free) in self.keys(username, ip) {
+            let failures = match cache.incr(&key, Some(self.window)).await {
+                Ok(n) => u32::try_from(n).unwrap_or(u32::MAX),
+                Err(e) => {
+                    tracing::warn!("login throttle unavailable: {:#}", e);
+                    continue;
+                }
+            };
+            if failures <= free {
+                continue;
+            }
+            let doublings = (failures - free - 1).min(31);
+            let delay = self
+                .base_delay
+                .saturating_mul(1 << doublings)
+                .min(self.max_lockout)
+                .max(Duration::from_secs(1));
+            let locked_until = now + delay.as_secs();
+            let Ok(value) = serde_json::to_vec(&locked_until) else {
+                continue;
+            };
+            let ttl = self.window.max(delay);
+            if let Err(e) = cache.set(&lock_key(&key), value, Some(ttl)).await {
+                tracing::warn!("login throttle unavailable: {:#}", e);
+            }
+        }
+    }
+
+    /// Clears the username's failures; the IP's are left to expire.
+    pub async fn record_success(&self, cache: Option<&DynCache>, username: &str) {
+        let Some(cache) = cache else {
+            return;
+        };
+        let (key, _) = self.keys(username, None).remove(0);
+        for key in [lock_key(&key), key] {
+            if let Err(e) = cache.delete(&key).await {
+                tracing::warn!("login throttle unavailable: {:#}", e);
+            }
+        }
+    }
+}
+
+/// Where the Unix second a key stays locked until is kept, next to its
+/// failure counter.
+fn lock_key(key: &str) -> String {
+    format!("{}:locked", key)
+}
+
+async fn load(cache: &DynCache, key: &str) -> Option<u64> {
+    match cache.get(key).await {
+        Ok(value) => value.and_then(|v| serde_json::from_slice(&v).ok()),
+        Err(e) => {
+            tracing::warn!("login throttle unavailable: {:#}", e);
+            None
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
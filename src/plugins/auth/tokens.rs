@@ -0,0 +1,487 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::models::LoginResponse;
+use crate::plugins::auth::repo;
+use anyhow::Context;
+use axum::http::{HeaderMap, StatusCode};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use uuid::Uuid;
+
+const REFRESH_PREFIX: &str = "rt_";
+/// `typ` of the token handed out between the password and the second factor.
+const CHALLENGE_TYP: &str = "mfa";
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    /// Token id, the key of its revocation entry.
+    pub jti: String,
+    /// Refresh token family the token was issued from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fid: Option<Uuid>,
+    /// Roles the user held when the token was issued.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Whether the session was started with a second factor.
+    #[serde(default)]
+    pub mfa: bool,
+    /// Set on tokens that are not access tokens, e.g. MFA challenges.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+}
+
+static SERVICE: OnceLock<TokenService> = OnceLock::new();
+
+/// Makes `service` the one every handler, extractor and the auth policy
+/// use. Returns false if one was installed, or built from the environment,
+/// before.
+pub fn install(service: TokenService) -> bool {
+    SERVICE.set(service).is_ok()
+}
+
+/// The installed [`TokenService`], built with [`TokenService::from_env`] on
+/// first use if there is none.
+pub fn service() -> Result<&'static TokenService, AppError> {
+    if let Some(service) = SERVICE.get() {
+        return Ok(service);
+    }
+    let service = TokenService::from_env().map_err(|e| {
+        tracing::error!("token service misconfigured: {:#}", e);
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "jwtNotConfigured")
+            .with_code("config_error")
+    })?;
+    Ok(SERVICE.get_or_init(|| service))
+}
+
+/// Signs and verifies access tokens.
+///
+/// Tokens are signed with the current key and carry its `kid`; tokens with
+/// the `kid` of a retired key added by [`TokenService::with_previous_key`]
+/// still verify, so keys can be rotated without logging everyone out.
+/// Tokens without a `kid` are checked against the current key.
+pub struct TokenService {
+    algorithm: Algorithm,
+    kid: String,
+    encoding: EncodingKey,
+    decoding: HashMap<String, DecodingKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+}
+
+impl TokenService {
+    fn with_key(
+        algorithm: Algorithm,
+        kid: String,
+        encoding: EncodingKey,
+        decoding: DecodingKey,
+    ) -> Self {
+        Self {
+            algorithm,
+            decoding: HashMap::from([(kid.clone(), decoding)]),
+            kid,
+            encoding,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(30),
+            access_ttl: Duration::from_secs(15 * 60),
+            refresh_ttl: Duration::from_secs(30 * 24 * 3600),
+        }
+    }
+
+    pub fn hs256(kid: impl Into<String>, secret: &[u8]) -> Self {
+        Self::with_key(
+            Algorithm::HS256,
+            kid.into(),
+            EncodingKey::from_secret(secret),
+            DecodingKey::from_secret(secret),
+        )
+    }
+
+    /// RS256 with a PEM private key and its PEM public key.
+    pub fn rs256(
+        kid: impl Into<String>,
+        private_pem: &[u8],
+        public_pem: &[u8],
+    ) -> anyhow::Result<Self> {
+        Ok(Self::with_key(
+            Algorithm::RS256,
+            kid.into(),
+            EncodingKey::from_rsa_pem(private_pem)?,
+            DecodingKey::from_rsa_pem(public_pem)?,
+        ))
+    }
+
+    /// EdDSA (Ed25519) with a PEM private key and its PEM public key.
+    pub fn eddsa(
+        kid: impl Into<String>,
+        private_pem: &[u8],
+        public_pem: &[u8],
+    ) -> anyhow::Result<Self> {
+        Ok(Self::with_key(
+            Algorithm::EdDSA,
+            kid.into(),
+            EncodingKey::from_ed_pem(private_pem)?,
+            DecodingKey::from_ed_pem(public_pem)?,
+        ))
+    }
+
+    /// Keeps accepting tokens signed with a retired key of the same
+    /// algorithm. For RS256 and EdDSA `key` is a PEM public key, for HS256
+    /// the secret.
+    pub fn with_previous_key(mut self, kid: impl Into<String>, key: &[u8]) -> anyhow::Result<Self> {
+        let decoding = match self.algorithm {
+            Algorithm::RS256 => DecodingKey::from_rsa_pem(key)?,
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(key)?,
+            _ => DecodingKey::from_secret(key),
+        };
+        let kid = kid.into();
+        anyhow::ensure!(
+            kid != self.kid,
+            "previous key id {} is the current one",
+            kid
+        );
+        self.decoding.insert(kid, decoding);
+        Ok(self)
+    }
+
+    /// Sets `iss` on issued tokens and requires it on verified ones.
+    pub fn with_issuer(mut self, issuer: Option<String>) -> Self {
+        self.issuer = issuer;
+        self
+    }
+
+    /// Sets `aud` on issued tokens and requires it on verified ones.
+    pub fn with_audience(mut self, audience: Option<String>) -> Self {
+        self.audience = audience;
+        self
+    }
+
+    /// Clock skew tolerated on `exp`, 30 seconds by default.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    pub fn with_ttls(mut self, access: Duration, refresh: Duration) -> Self {
+        self.access_ttl = access;
+        self.refresh_ttl = refresh;
+        self
+    }
+
+    /// Reads the configuration:
+    ///
+    /// - `JWT_ALG`: `HS256` (default), `RS256` or `EdDSA`
+    /// - `JWT_KID`: id of the signing key (default `default`)
+    /// - `JWT_SECRET` for HS256; `JWT_PRIVATE_KEY_FILE` and
+    ///   `JWT_PUBLIC_KEY_FILE` (PEM) otherwise
+    /// - `JWT_PREVIOUS_KEYS`: retired keys still accepted, as
+    ///   `kid=secret` (HS256) or `kid=/path/to/public.pem`, comma separated
+    /// - `JWT_ISSUER`, `JWT_AUDIENCE`, `JWT_LEEWAY_SECS` (default 30)
+    /// - `JWT_ACCESS_TTL_SECS` (default 15 minutes) and
+    ///   `JWT_REFRESH_TTL_SECS` (default 30 days)
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let read_pem = |name: &str| -> anyhow::Result<Vec<u8>> {
+            let path = var(name).with_context(|| format!("{} must be set", name))?;
+            std::fs::read(&path).with_context(|| format!("reading {} ({})", name, path))
+        };
+        let kid = var("JWT_KID").unwrap_or_else(|| "default".to_string());
+        let mut service = match var("JWT_ALG").as_deref().unwrap_or("HS256") {
+            "HS256" => {
+                let secret = var("JWT_SECRET").context("JWT_SECRET must be set")?;
+                Self::hs256(kid, secret.as_bytes())
+            }
+            "RS256" => Self::rs256(
+                kid,
+                &read_pem("JWT_PRIVATE_KEY_FILE")?,
+                &read_pem("JWT_PUBLIC_KEY_FILE")?,
+            )?,
+            "EdDSA" => Self::eddsa(
+                kid,
+                &read_pem("JWT_PRIVATE_KEY_FILE")?,
+                &read_pem("JWT_PUBLIC_KEY_FILE")?,
+            )?,
+            other => anyhow::bail!("unsupported JWT_ALG {}", other),
+        };
+        for entry in var("JWT_PREVIOUS_KEYS").unwrap_or_default().split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (kid, value) = entry
+                .split_once('=')
+                .context("JWT_PREVIOUS_KEYS entries must be kid=value")?;
+            let key = if service.algorithm == Algorithm::HS256 {
+                value.as_bytes().to_vec()
+            } else {
+                std::fs::read(value).with_context(|| format!("reading previous key {}", kid))?
+            };
+            service = service.with_previous_key(kid, &key)?;
+        }
+        let secs = |name: &str, default: u64| -> anyhow::Result<Duration> {
+            Ok(Duration::from_secs(match var(name) {
+                Some(v) => v
+                    .parse()
+                    .with_context(|| format!("{} is not a number", name))?,
+                None => default,
+            }))
+        };
+        Ok(service
+            .with_issuer(var("JWT_ISSUER"))
+            .with_audience(var("JWT_AUDIENCE"))
+            .with_leeway(secs("JWT_LEEWAY_SECS", 30)?)
+            .with_ttls(
+                secs("JWT_ACCESS_TTL_SECS", 15 * 60)?,
+                secs("JWT_REFRESH_TTL_SECS", 30 * 24 * 3600)?,
+            ))
+    }
+
+    pub fn access_ttl(&self) -> Duration {
+        self.access_ttl
+    }
+
+    pub fn refresh_ttl(&self) -> Duration {
+        self.refresh_ttl
+    }
+
+    /// Signs an access token for `user_id` holding `roles`, issued from
+    /// refresh token family `family_id`; `mfa` when the session was started
+    /// with a second factor.
+    pub fn issue_access(
+        &self,
+        user_id: Uuid,
+        roles: Vec<String>,
+        family_id: Option<Uuid>,
+        mfa: bool,
+    ) -> Result<String, AppError> {
+        let mut claims = self.claims(user_id, self.access_ttl);
+        claims.fid = family_id;
+        claims.roles = roles;
+        claims.mfa = mfa;
+        self.sign(&claims)
+    }
+
+    /// Signs a short-lived token proving `user_id` passed the password step
+    /// of a login, to be exchanged with a second factor.
+    pub fn issue_challenge(&self, user_id: Uuid) -> Result<String, AppError> {
+        let mut claims = self.claims(user_id, CHALLENGE_TTL);
+        claims.typ = Some(CHALLENGE_TYP.to_string());
+        self.sign(&claims)
+    }
+
+    fn claims(&self, user_id: Uuid, ttl: Duration) -> Claims {
+        let now = chrono::Utc::now().timestamp();
+        Claims {
+            sub: user_id.to_string(),
+            exp: (now + ttl.as_secs() as i64) as usize,
+            iat: now as usize,
+            jti: Uuid::new_v4().to_string(),
+            fid: None,
+            roles: Vec::new(),
+            mfa: false,
+            typ: None,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+        }
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String, AppError> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        encode(&header, claims, &self.encoding)
+            .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+
+    /// Checks the signature, `exp`, and `iss`/`aud` when configured, and that
+    /// `token` is an access token.
+    pub fn verify(&self, token: &str) -> Result<Claims, AppError> {
+        let claims = self.decode(token)?;
+        if claims.typ.is_some() {
+            return Err(invalid_token());
+        }
+        Ok(claims)
+    }
+
+    /// Like [`TokenService::verify`], for tokens from
+    /// [`TokenService::issue_challenge`]. Returns the user.
+    pub fn verify_challenge(&self, token: &str) -> Result<Uuid, AppError> {
+        let claims = self.decode(token)?;
+        if claims.typ.as_deref() != Some(CHALLENGE_TYP) {
+            return Err(invalid_token());
+        }
+        Uuid::parse_str(&claims.sub).map_err(|_| invalid_token())
+    }
+
+    fn decode(&self, token: &str) -> Result<Claims, AppError> {
+        let header = decode_header(token).map_err(|_| invalid_token())?;
+        let kid = header.kid.as_deref().unwrap_or(&self.kid);
+        let key = self.decoding.get(kid).ok_or_else(invalid_token)?;
+
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = self.leeway.as_secs();
+        let mut required = vec!["exp", "sub"];
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+        decode::<Claims>(token, key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => {
+                    AppError::new(StatusCode::UNAUTHORIZED, "token expired")
+                        .with_code("token_expired")
+                }
+                _ => invalid_token(),
+            })
+    }
+}
+
+/// Starts a session for `user_id`: an access token and the first refresh
+/// token of a new family. `mfa` carries over to tokens from refreshes.
+pub async fn issue_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    mfa: bool,
+) -> Result<LoginResponse, AppError> {
+    issue_pair(pool, user_id, Uuid::new_v4(), mfa).await
+}
+
+/// Exchanges `presented` for a new token pair of the same family. A refresh
+/// token already exchanged before revokes the whole family: either it leaked
+/// or the client is replaying it.
+pub async fn refresh(pool: &PgPool, presented: &str) -> Result<LoginResponse, AppError> {
+    let invalid = || {
+        AppError::new(StatusCode::UNAUTHORIZED, "invalidRefreshToken")
+            .with_code("invalid_refresh_token")
+    };
+    let Some(token) = repo::find_refresh_token(pool, &hash_token(presented)).await? else {
+        return Err(invalid());
+    };
+    if token.revoked_at.is_some() || token.expires_at <= chrono::Utc::now() {
+        return Err(invalid());
+    }
+    if token.used_at.is_some() || !repo::mark_refresh_token_used(pool, token.id).await? {
+        tracing::warn!(
+            "refresh token reuse for user {}, revoking family {}",
+            token.user_id,
+            token.family_id
+        );
+        revoke_family(pool, token.family_id).await?;
+        return Err(
+            AppError::new(StatusCode::UNAUTHORIZED, "refreshTokenReused")
+                .with_code("refresh_token_reused"),
+        );
+    }
+    issue_pair(pool, token.user_id, token.family_id, token.mfa).await
+}
+
+/// Revokes the access token `claims` belong to and, with it, its family.
+pub async fn logout(pool: &PgPool, claims: &Claims) -> Result<(), AppError> {
+    let expires_at =
+        chrono::DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(chrono::Utc::now);
+    repo::revoke_access_token(pool, &claims.jti, expires_at).await?;
+    if let Some(family_id) = claims.fid {
+        revoke_family(pool, family_id).await?;
+    }
+    Ok(())
+}
+
+/// Revokes every refresh token of the family and, with them, the access
+/// tokens issued from it.
+pub async fn revoke_family(pool: &PgPool, family_id: Uuid) -> Result<(), AppError> {
+    repo::revoke_refresh_family(pool, family_id).await
+}
+
+/// Validates the `Authorization: Bearer` token in `headers` and checks it
+/// has not been revoked.
+pub async fn bearer_claims(headers: &HeaderMap, pool: &PgPool) -> Result<Claims, AppError> {
+    let auth_hdr = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| {
+            AppError::new(StatusCode::UNAUTHORIZED, "missing authorization")
+                .with_code("missing_token")
+        })?;
+    let Some(token) = auth_hdr.strip_prefix("Bearer ") else {
+        return Err(
+            AppError::new(StatusCode::UNAUTHORIZED, "invalid authorization header")
+                .with_code("invalid_token"),
+        );
+    };
+    let claims = service()?.verify(token)?;
+    if repo::access_token_revoked(pool, &claims.jti, claims.fid).await? {
+        return Err(
+            AppError::new(StatusCode::UNAUTHORIZED, "tokenRevoked").with_code("token_revoked")
+        );
+    }
+    Ok(claims)
+}
+
+async fn issue_pair(
+    pool: &PgPool,
+    user_id: Uuid,
+    family_id: Uuid,
+    mfa: bool,
+) -> Result<LoginResponse, AppError> {
+    let service = service()?;
+    let roles = crate::plugins::users::repo::user_roles(pool, user_id).await?;
+    let token = service.issue_access(user_id, roles, Some(family_id), mfa)?;
+
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let refresh_token = format!("{}{}", REFRESH_PREFIX, URL_SAFE_NO_PAD.encode(secret));
+    let refresh_expires_at = chrono::Utc::now()
+        + chrono::Duration::from_std(service.refresh_ttl())
+            .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    repo::insert_refresh_token(
+        pool,
+        family_id,
+        user_id,
+        &hash_token(&refresh_token),
+        refresh_expires_at,
+        mfa,
+    )
+    .await?;
+
+    Ok(LoginResponse {
+        token,
+        refresh_token,
+        token_type: "Bearer",
+        expires_in: service.access_ttl().as_secs(),
+    })
+}
+
+fn invalid_token() -> AppError {
+    AppError::new(StatusCode::UNAUTHORIZED, "invalid token").with_code("invalid_token")
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
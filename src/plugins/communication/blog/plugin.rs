@@ -1,5 +1,8 @@
 use crate::kernel::Plugin;
+use crate::plugins::auth::policy::{Access, AccessRule};
+use crate::plugins::auth::rbac::{Permission, WriteBlog};
 use crate::plugins::communication::blog::handlers::*;
+use axum::http::Method;
 use axum::{Extension, Router, routing::delete, routing::get, routing::post, routing::put};
 use sqlx::PgPool;
 
@@ -29,4 +32,13 @@ impl Plugin for BlogPlugin {
     fn name(&self) -> &'static str {
         "communication/blog"
     }
+
+    fn access_rules(&self) -> Vec<AccessRule> {
+        let write = Access::Permission(WriteBlog::NAME);
+        vec![
+            AccessRule::on(Method::POST, "/", write),
+            AccessRule::on(Method::PUT, "/:id", write),
+            AccessRule::on(Method::DELETE, "/:id", write),
+        ]
+    }
 }
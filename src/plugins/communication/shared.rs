@@ -1,11 +1,149 @@
+use crate::http_error::AppError;
+use axum::http::StatusCode;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ListResponse<T> {
     pub items: Vec<T>,
-    pub page: u32,
+    /// Absent when the page was requested with a cursor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
     pub per_page: u32,
-    pub total: i64,
-    pub total_pages: i64,
+    /// Absent unless counting was requested (the default for page-based requests).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<i64>,
     pub has_more: bool,
+    /// Pass as `cursor` to fetch the rows after this page; null on the last page.
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// Position in a listing ordered by `created_at DESC, id DESC`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub id: uuid::Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self, AppError> {
+        let invalid =
+            || AppError::new(StatusCode::BAD_REQUEST, "invalidCursor").with_code("invalid_cursor");
+        let bytes = URL_SAFE_NO_PAD.decode(raw).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (micros, id) = text.split_once(':').ok_or_else(invalid)?;
+        let created_at = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(chrono::DateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?;
+        let id = uuid::Uuid::parse_str(id).map_err(|_| invalid())?;
+        Ok(Self { created_at, id })
+    }
+}
+
+/// Pagination parameters shared by the list endpoints: either `page` based
+/// (OFFSET) or `cursor` based (keyset on `created_at, id`).
+#[derive(Debug)]
+pub struct PageRequest {
+    pub page: Option<u32>,
+    pub per_page: u32,
+    pub cursor: Option<Cursor>,
+    /// Run the `COUNT(*)` query for `total` and `total_pages`.
+    pub count: bool,
+}
+
+impl PageRequest {
+    pub fn new(
+        page: Option<u32>,
+        per_page: Option<u32>,
+        cursor: Option<&str>,
+        count: Option<bool>,
+    ) -> Result<Self, AppError> {
+        let per_page = per_page.unwrap_or(20).clamp(1, 100);
+        let cursor = cursor.map(Cursor::decode).transpose()?;
+        if cursor.is_some() && page.is_some() {
+            return Err(
+                AppError::new(StatusCode::BAD_REQUEST, "cursorAndPageAreExclusive")
+                    .with_code("invalid_cursor"),
+            );
+        }
+        let page = match cursor {
+            Some(_) => None,
+            None => Some(page.unwrap_or(1).max(1)),
+        };
+        Ok(Self {
+            page,
+            per_page,
+            cursor,
+            count: count.unwrap_or(cursor.is_none()),
+        })
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.page.map_or(0, |p| ((p - 1) * self.per_page) as i64)
+    }
+
+    /// Rows to fetch: one more than a page, to learn whether another follows.
+    pub fn limit(&self) -> i64 {
+        self.per_page as i64 + 1
+    }
+}
+
+impl<T> ListResponse<T> {
+    /// Builds the response from up to [`PageRequest::limit`] rows.
+    pub fn from_page(
+        mut items: Vec<T>,
+        req: &PageRequest,
+        total: Option<i64>,
+        cursor_of: impl Fn(&T) -> Cursor,
+    ) -> Self {
+        let has_more = items.len() > req.per_page as usize;
+        items.truncate(req.per_page as usize);
+        let next_cursor = if has_more {
+            items.last().map(|last| cursor_of(last).encode())
+        } else {
+            None
+        };
+        let total_pages = total.map(|total| {
+            if total == 0 {
+                0
+            } else {
+                ((total as f64) / (req.per_page as f64)).ceil() as i64
+            }
+        });
+        Self {
+            items,
+            page: req.page,
+            per_page: req.per_page,
+            total,
+            total_pages,
+            has_more,
+            next_cursor,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> ListResponse<U> {
+        ListResponse {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            per_page: self.per_page,
+            total: self.total,
+            total_pages: self.total_pages,
+            has_more: self.has_more,
+            next_cursor: self.next_cursor,
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use crate::http_error::AppError;
-use crate::plugins::communication::shared::ListResponse;
+use crate::plugins::communication::shared::{Cursor, ListResponse, PageRequest};
 use crate::plugins::communication::stories::models::{StoryCreate, StoryDto, StoryUpdate};
 use crate::plugins::communication::stories::repo;
 use axum::http::StatusCode;
@@ -11,6 +11,10 @@ use sqlx::PgPool;
 pub struct ListQuery {
     pub page: Option<u32>,
     pub per_page: Option<u32>,
+    /// `next_cursor` of a previous page; excludes `page`.
+    pub cursor: Option<String>,
+    /// Include `total` and `total_pages`; defaults to true without a cursor.
+    pub count: Option<bool>,
     pub is_active: Option<bool>,
     pub created_by: Option<String>,
 }
@@ -45,13 +49,13 @@ pub async fn list_stories(
     Extension(pool): Extension<PgPool>,
     axum::extract::Query(q): axum::extract::Query<ListQuery>,
 ) -> Result<Json<ListResponse<StoryDto>>, AppError> {
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) * per_page) as i64;
+    let req = PageRequest::new(q.page, q.per_page, q.cursor.as_deref(), q.count)?;
 
     enum Param {
         Bool(bool),
         Str(String),
+        Time(chrono::DateTime<chrono::Utc>),
+        Uuid(uuid::Uuid),
     }
     let mut where_clauses: Vec<String> = Vec::new();
     let mut params: Vec<Param> = Vec::new();
@@ -69,10 +73,22 @@ pub async fn list_stories(
     } else {
         where_clauses.join(" AND ")
     };
+    let count_params = params.len();
+
+    let mut page_where_sql = where_sql.clone();
+    if let Some(cursor) = req.cursor {
+        page_where_sql.push_str(&format!(
+            " AND (created_at, id) < (${}, ${})",
+            params.len() + 1,
+            params.len() + 2
+        ));
+        params.push(Param::Time(cursor.created_at));
+        params.push(Param::Uuid(cursor.id));
+    }
 
     let items_sql = format!(
-        "SELECT id, title, media_url, caption, is_active, created_at, expires_at, created_by FROM stories WHERE {} ORDER BY created_at DESC LIMIT ${} OFFSET ${}",
-        where_sql,
+        "SELECT id, title, media_url, caption, is_active, created_at, expires_at, created_by FROM stories WHERE {} ORDER BY created_at DESC, id DESC LIMIT ${} OFFSET ${}",
+        page_where_sql,
         params.len() + 1,
         params.len() + 2
     );
@@ -85,40 +101,45 @@ pub async fn list_stories(
             Param::Str(s) => {
                 items_q = items_q.bind(s.clone());
             }
+            Param::Time(t) => {
+                items_q = items_q.bind(*t);
+            }
+            Param::Uuid(u) => {
+                items_q = items_q.bind(*u);
+            }
         }
     }
-    items_q = items_q.bind(per_page as i64).bind(offset);
+    items_q = items_q.bind(req.limit()).bind(req.offset());
     let items: Vec<StoryDto> = items_q.fetch_all(&pool).await.map_err(AppError::from)?;
 
-    let count_sql = format!("SELECT COUNT(*) FROM stories WHERE {}", where_sql);
-    let mut count_q = sqlx::query_scalar::<_, i64>(&count_sql);
-    for p in &params {
-        match p {
-            Param::Bool(b) => {
-                count_q = count_q.bind(*b);
-            }
-            Param::Str(s) => {
-                count_q = count_q.bind(s.clone());
+    let total = if req.count {
+        let count_sql = format!("SELECT COUNT(*) FROM stories WHERE {}", where_sql);
+        let mut count_q = sqlx::query_scalar::<_, i64>(&count_sql);
+        for p in &params[..count_params] {
+            match p {
+                Param::Bool(b) => {
+                    count_q = count_q.bind(*b);
+                }
+                Param::Str(s) => {
+                    count_q = count_q.bind(s.clone());
+                }
+                Param::Time(t) => {
+                    count_q = count_q.bind(*t);
+                }
+                Param::Uuid(u) => {
+                    count_q = count_q.bind(*u);
+                }
             }
         }
-    }
-    let total: i64 = count_q.fetch_one(&pool).await.map_err(AppError::from)?;
-
-    let fetched = items.len() as i64;
-    let has_more = offset + fetched < total;
-    let total_pages = if total == 0 {
-        0
+        Some(count_q.fetch_one(&pool).await.map_err(AppError::from)?)
     } else {
-        ((total as f64) / (per_page as f64)).ceil() as i64
-    };
-    let resp = ListResponse {
-        items,
-        page,
-        per_page,
-        total,
-        total_pages,
-        has_more,
+        None
     };
+
+    let resp = ListResponse::from_page(items, &req, total, |item| Cursor {
+        created_at: item.created_at,
+        id: item.id,
+    });
     Ok(Json(resp))
 }
 
@@ -1,4 +1,5 @@
 use crate::kernel::Plugin;
+use crate::plugins::auth::policy::Access;
 use axum::{Json, Router, routing::get};
 use serde::Serialize;
 
@@ -24,6 +25,10 @@ impl Plugin for HealthPlugin {
         "health"
     }
 
+    fn default_access(&self) -> Access {
+        Access::Public
+    }
+
     async fn on_start(&self) {
         tracing::info!("health plugin started");
     }
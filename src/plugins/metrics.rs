@@ -1,4 +1,5 @@
 use axum::{Router, routing::get};
+use prometheus::core::Collector;
 use prometheus::{
     Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
 };
@@ -42,6 +43,14 @@ impl MetricsPlugin {
         }
     }
 
+    /// Exposes a plugin's own collectors under `/metrics`. Collectors whose
+    /// names are already taken are ignored.
+    pub fn register(&self, collector: Box<dyn Collector>) {
+        if let Err(e) = self.registry.register(collector) {
+            tracing::warn!("metrics collector not registered: {}", e);
+        }
+    }
+
     pub fn router(&self) -> Router {
         let reg = self.registry.clone();
         Router::new().route(
@@ -0,0 +1,319 @@
+use crate::http_error::AppError;
+use crate::plugins::metrics::MetricsPlugin;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::crypto::{SigScheme, content_hash};
+use crate::plugins::tangle::models::{AuditReport, NewBlock, TangleExportRow};
+use crate::plugins::tangle::{repo, service};
+use prometheus::{IntCounter, IntCounterVec, IntGaugeVec, Opts};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// What the auditor can find wrong with a stored block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// Parents, signature scheme or supersession markers no longer parse.
+    Malformed,
+    /// The stored content hash does not match the stored content.
+    HashMismatch,
+    /// The signature does not verify under the rules blocks are accepted with.
+    InvalidSignature,
+    /// A parent is not in `tangle_blocks`.
+    MissingParent,
+}
+
+impl FindingKind {
+    pub const ALL: [FindingKind; 4] = [
+        FindingKind::Malformed,
+        FindingKind::HashMismatch,
+        FindingKind::InvalidSignature,
+        FindingKind::MissingParent,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FindingKind::Malformed => "malformed",
+            FindingKind::HashMismatch => "hash_mismatch",
+            FindingKind::InvalidSignature => "invalid_signature",
+            FindingKind::MissingParent => "missing_parent",
+        }
+    }
+}
+
+impl std::str::FromStr for FindingKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FindingKind::ALL
+            .into_iter()
+            .find(|k| k.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Prometheus collectors of the auditor, registered with [`MetricsPlugin`]
+/// when the kernel exposes metrics.
+#[derive(Clone)]
+pub struct AuditMetrics {
+    pub blocks_checked: IntCounter,
+    pub findings: IntCounterVec,
+    pub open_findings: IntGaugeVec,
+    pub passes: IntCounter,
+}
+
+impl AuditMetrics {
+    pub fn new() -> Self {
+        AuditMetrics {
+            blocks_checked: IntCounter::new(
+                "tangle_audit_blocks_checked_total",
+                "Stored tangle blocks re-verified by the auditor",
+            )
+            .expect("counter"),
+            findings: IntCounterVec::new(
+                Opts::new(
+                    "tangle_audit_findings_total",
+                    "Audit findings opened, by kind",
+                ),
+                &["kind"],
+            )
+            .expect("counter"),
+            open_findings: IntGaugeVec::new(
+                Opts::new(
+                    "tangle_audit_open_findings",
+                    "Unresolved audit findings, by kind",
+                ),
+                &["kind"],
+            )
+            .expect("gauge"),
+            passes: IntCounter::new(
+                "tangle_audit_passes_total",
+                "Completed audit passes over the whole tangle",
+            )
+            .expect("counter"),
+        }
+    }
+
+    pub fn register(&self, metrics: &MetricsPlugin) {
+        metrics.register(Box::new(self.blocks_checked.clone()));
+        metrics.register(Box::new(self.findings.clone()));
+        metrics.register(Box::new(self.open_findings.clone()));
+        metrics.register(Box::new(self.passes.clone()));
+    }
+}
+
+impl Default for AuditMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Re-verifies stored blocks in seq order, one batch per tick, and records
+/// what it finds in `tangle_audit_findings`.
+pub struct TangleAuditor {
+    pool: PgPool,
+    config: Arc<TangleConfig>,
+    pub metrics: AuditMetrics,
+    shutdown: watch::Sender<bool>,
+}
+
+impl TangleAuditor {
+    pub fn new(pool: PgPool, config: Arc<TangleConfig>) -> Self {
+        Self {
+            pool,
+            config,
+            metrics: AuditMetrics::new(),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// Starts the audit loop if an interval is configured. The loop finishes
+    /// the batch in hand and exits after [`TangleAuditor::stop`].
+    pub fn spawn(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.config.audit_interval_secs == 0 {
+            return None;
+        }
+        let auditor = self.clone();
+        let mut shutdown = self.shutdown.subscribe();
+        let every = Duration::from_secs(self.config.audit_interval_secs);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            let mut after_seq = 0;
+            while !*shutdown.borrow_and_update() {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.changed() => break,
+                }
+                match auditor.audit_batch(after_seq).await {
+                    Ok((report, Some(last_seq))) => {
+                        after_seq = last_seq;
+                        if report.findings > 0 {
+                            tracing::warn!(
+                                "tangle audit found {} problems up to seq {}",
+                                report.findings,
+                                last_seq
+                            );
+                        }
+                    }
+                    Ok((_, None)) => {
+                        after_seq = 0;
+                        auditor.metrics.passes.inc();
+                    }
+                    Err(e) => tracing::warn!("tangle audit failed: {}", e.message),
+                }
+            }
+        }))
+    }
+
+    pub fn stop(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Audits every stored block now.
+    pub async fn run_pass(&self) -> Result<AuditReport, AppError> {
+        let mut total = AuditReport::default();
+        let mut after_seq = 0;
+        while let (report, Some(last_seq)) = self.audit_batch(after_seq).await? {
+            total.checked += report.checked;
+            total.findings += report.findings;
+            total.resolved += report.resolved;
+            after_seq = last_seq;
+        }
+        self.metrics.passes.inc();
+        Ok(total)
+    }
+
+    /// Audits the next batch of blocks after `after_seq`. Returns the last
+    /// seq audited, or `None` once the end of the tangle is reached.
+    async fn audit_batch(&self, after_seq: i64) -> Result<(AuditReport, Option<i64>), AppError> {
+        let rows = repo::export_batch(&self.pool, after_seq, self.config.audit_batch_size).await?;
+        let Some(last_seq) = rows.last().map(|r| r.seq) else {
+            return Ok((AuditReport::default(), None));
+        };
+
+        let mut parents: Vec<Uuid> = rows
+            .iter()
+            .flat_map(|r| &r.block.parents)
+            .filter_map(|p| Uuid::parse_str(p).ok())
+            .collect();
+        parents.sort();
+        parents.dedup();
+        let missing = repo::find_missing_parents(&self.pool, &parents).await?;
+
+        let mut report = AuditReport {
+            checked: rows.len() as u64,
+            ..AuditReport::default()
+        };
+        let mut open: Vec<(Uuid, &str)> = Vec::new();
+        for row in &rows {
+            for (kind, detail) in check_block(&self.config, row, &missing) {
+                let opened = repo::record_audit_finding(
+                    &self.pool,
+                    row.block.id,
+                    row.seq,
+                    kind.as_str(),
+                    &detail,
+                )
+                .await?;
+                if opened {
+                    self.metrics
+                        .findings
+                        .with_label_values(&[kind.as_str()])
+                        .inc();
+                }
+                report.findings += 1;
+                open.push((row.block.id, kind.as_str()));
+            }
+        }
+        let ids: Vec<Uuid> = rows.iter().map(|r| r.block.id).collect();
+        report.resolved = repo::resolve_audit_findings(&self.pool, &ids, &open).await?;
+
+        self.metrics.blocks_checked.inc_by(report.checked);
+        self.refresh_open_findings().await?;
+        Ok((report, Some(last_seq)))
+    }
+
+    async fn refresh_open_findings(&self) -> Result<(), AppError> {
+        let counts = repo::open_audit_finding_counts(&self.pool).await?;
+        for kind in FindingKind::ALL {
+            let open = counts
+                .iter()
+                .find(|(k, _)| k == kind.as_str())
+                .map_or(0, |(_, n)| *n);
+            self.metrics
+                .open_findings
+                .with_label_values(&[kind.as_str()])
+                .set(open);
+        }
+        Ok(())
+    }
+}
+
+/// Re-runs the checks a block passed on insert against its stored form.
+fn check_block(
+    config: &TangleConfig,
+    row: &TangleExportRow,
+    missing_parents: &[Uuid],
+) -> Vec<(FindingKind, String)> {
+    let block = &row.block;
+    let mut findings = Vec::new();
+
+    let absent: Vec<String> = block
+        .parents
+        .iter()
+        .filter(|p| Uuid::parse_str(p).is_ok_and(|p| missing_parents.contains(&p)))
+        .cloned()
+        .collect();
+    if !absent.is_empty() {
+        findings.push((FindingKind::MissingParent, absent.join(",")));
+    }
+
+    let parents = match service::parse_parents(Some(block.id), &block.parents) {
+        Ok(parents) => parents,
+        Err(e) => {
+            findings.push((FindingKind::Malformed, e.message));
+            return findings;
+        }
+    };
+    let Ok(sig_scheme) = SigScheme::try_from(block.sig_scheme) else {
+        findings.push((
+            FindingKind::Malformed,
+            format!("unknownSigScheme {}", block.sig_scheme),
+        ));
+        return findings;
+    };
+    let hash = content_hash(
+        &parents,
+        &block.data,
+        &block.public_key,
+        block.timestamp_ms,
+        block.supersedes,
+        block.tombstone,
+    );
+    // blocks stored before content hashes existed have none to compare
+    if block.hash.as_deref().is_some_and(|h| h != hash) {
+        findings.push((FindingKind::HashMismatch, hex::encode(hash)));
+    }
+
+    let stored = NewBlock {
+        id: block.id,
+        parents,
+        data: block.data.clone(),
+        signature: block.signature.clone(),
+        public_key: block.public_key.clone(),
+        sig_scheme,
+        timestamp_ms: block.timestamp_ms,
+        supersedes: block.supersedes,
+        tombstone: block.tombstone,
+        hash,
+    };
+    if let Err(e) = service::check_supersession_markers(&stored) {
+        findings.push((FindingKind::Malformed, e.message));
+    }
+    if let Err(e) = service::verify_signature(config, &stored) {
+        findings.push((FindingKind::InvalidSignature, e.message));
+    }
+    findings
+}
@@ -0,0 +1,141 @@
+use crate::http_error::AppError;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::crypto::{
+    InclusionProof, block_leaf_hash, checkpoint_chain_hash, merkle_proof, merkle_root,
+    verify_inclusion,
+};
+use crate::plugins::tangle::models::{
+    CheckpointRow, TangleBlockCreate, TangleExportRow, TangleInclusionProof,
+};
+use crate::plugins::tangle::repo;
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Serializes checkpoint creation across kernels sharing the database.
+const CHECKPOINT_LOCK: i64 = 0x7461_6e67_6c65_6370; // "tanglecp"
+
+/// Merkle leaf of a stored block, computed from its archive form so that
+/// auditors can recompute it from `GET /tangle/export`.
+pub fn leaf_of(row: &TangleExportRow) -> [u8; 32] {
+    block_leaf_hash(&archived_of(row))
+}
+
+fn archived_of(row: &TangleExportRow) -> serde_json::Value {
+    serde_json::to_value(TangleBlockCreate::from(row.block.clone()))
+        .unwrap_or(serde_json::Value::Null)
+}
+
+/// Commits to every block stored since the last checkpoint, up to the
+/// highest seq no transaction in flight can still commit below.
+/// Returns `None` when there is nothing new to cover.
+pub async fn create_checkpoint(pool: &PgPool) -> Result<Option<CheckpointRow>, AppError> {
+    // taken before `tx` so this never holds two pool connections at once
+    let watermark = repo::committed_seq(pool, 0).await?;
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(CHECKPOINT_LOCK)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+
+    let previous = repo::latest_checkpoint(&mut *tx).await?;
+    let from_seq = previous.as_ref().map_or(0, |c| c.to_seq);
+    let Some(to_seq) = watermark.filter(|&seq| seq > from_seq) else {
+        return Ok(None);
+    };
+
+    let rows = repo::blocks_in_seq_range(&mut *tx, from_seq, to_seq).await?;
+    let leaves: Vec<[u8; 32]> = rows.iter().map(leaf_of).collect();
+    let root = merkle_root(&leaves);
+    let previous_chain: Option<[u8; 32]> = previous.and_then(|c| c.chain.try_into().ok());
+    let chain = checkpoint_chain_hash(previous_chain.as_ref(), &root, from_seq, to_seq);
+
+    let row = repo::insert_checkpoint(
+        &mut tx,
+        from_seq,
+        to_seq,
+        leaves.len() as i64,
+        &root,
+        &chain,
+    )
+    .await?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(Some(row))
+}
+
+/// Proves that block `id` is covered by its checkpoint. The proof is built
+/// from the blocks as they are stored now, so it only verifies against the
+/// checkpoint root if none of them changed since.
+pub async fn inclusion_proof(pool: &PgPool, id: Uuid) -> Result<TangleInclusionProof, AppError> {
+    let seq = repo::block_seq(pool, id).await?.ok_or_else(|| {
+        AppError::new(StatusCode::NOT_FOUND, "blockNotFound").with_code("not_found")
+    })?;
+    let checkpoint = repo::checkpoint_covering(pool, seq).await?.ok_or_else(|| {
+        AppError::new(StatusCode::NOT_FOUND, "blockNotYetCheckpointed")
+            .with_code("not_checkpointed")
+    })?;
+    let rows = repo::blocks_in_seq_range(pool, checkpoint.from_seq, checkpoint.to_seq).await?;
+    let index = rows.iter().position(|r| r.seq == seq).ok_or_else(|| {
+        AppError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "checkpointRangeIncomplete",
+        )
+        .with_code("checkpoint_mismatch")
+    })?;
+    let leaves: Vec<[u8; 32]> = rows.iter().map(leaf_of).collect();
+    let proof = merkle_proof(&leaves, index).ok_or_else(|| {
+        AppError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "checkpointRangeIncomplete",
+        )
+        .with_code("checkpoint_mismatch")
+    })?;
+    // the stored root, not the recomputed one: a tampered block fails to verify
+    let proof = InclusionProof {
+        root: hex::encode(&checkpoint.root),
+        ..proof
+    };
+    if !verify_inclusion(&archived_of(&rows[index]), &proof) {
+        tracing::warn!(
+            "tangle checkpoint {} no longer matches its blocks (seq {}..={})",
+            checkpoint.id,
+            checkpoint.from_seq + 1,
+            checkpoint.to_seq
+        );
+    }
+    Ok(TangleInclusionProof {
+        block_id: id,
+        seq,
+        proof,
+        checkpoint: checkpoint.into(),
+    })
+}
+
+/// Starts the periodic checkpoint loop if an interval is configured.
+pub fn spawn(pool: PgPool, config: Arc<TangleConfig>) -> Option<JoinHandle<()>> {
+    if config.checkpoint_interval_secs == 0 {
+        return None;
+    }
+    let every = Duration::from_secs(config.checkpoint_interval_secs);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            match create_checkpoint(&pool).await {
+                Ok(Some(c)) => tracing::info!(
+                    "tangle checkpoint {} covers seq {}..={} ({} blocks)",
+                    c.id,
+                    c.from_seq + 1,
+                    c.to_seq,
+                    c.block_count
+                ),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("tangle checkpoint failed: {}", e.message),
+            }
+        }
+    }))
+}
@@ -0,0 +1,192 @@
+use crate::plugins::tangle::models::Confirmation;
+use crate::plugins::tangle::tips::TipStrategy;
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct TangleConfig {
+    /// Hold blocks with unknown parents in `tangle_pending_blocks` instead of rejecting them.
+    pub quarantine_orphans: bool,
+    /// Seconds between sweeps promoting quarantined blocks whose parents
+    /// have all been stored; 0 disables them.
+    pub pending_sweep_secs: u64,
+    /// Reject any block whose public key, signature or signed payload does not
+    /// check out. When off, blocks whose key is not a 32-byte ed25519 key are
+    /// stored unverified.
+    pub strict_signatures: bool,
+    /// Accept `sig_scheme: 0` blocks whose signature only covers `data`.
+    /// Such a signature does not bind the parents, so anyone can re-parent
+    /// a signed payload; only enable this while migrating old clients.
+    pub allow_legacy_signatures: bool,
+    /// Derive block ids from their content hash (see `crypto::content_address`)
+    /// instead of accepting client-chosen or random UUIDs.
+    pub content_addressed_ids: bool,
+    /// Strategy used by `GET /tangle/tips/select` when none is requested.
+    pub tip_strategy: TipStrategy,
+    pub confirmation: ConfirmationThresholds,
+    /// Only accept `POST /tangle/blocks` from authenticated users signing with
+    /// one of their active registered keys. Imported and synced blocks are
+    /// not affected.
+    pub require_registered_keys: bool,
+    /// Base URLs of other kernels to pull blocks from.
+    pub peers: Vec<String>,
+    /// Sent as `x-api-key` to peers that require one.
+    pub peer_api_key: Option<String>,
+    /// Seconds between background syncs with `peers`; 0 disables the loop.
+    pub sync_interval_secs: u64,
+    /// Seconds between automatic Merkle checkpoints; 0 disables them.
+    pub checkpoint_interval_secs: u64,
+    /// Seconds between batches of the background auditor; 0 disables it.
+    pub audit_interval_secs: u64,
+    /// Blocks re-verified per audit batch.
+    pub audit_batch_size: i64,
+    /// Reject blocks whose `data` has no `type` with a registered schema.
+    /// When off, such blocks are stored unvalidated.
+    pub require_data_schema: bool,
+    /// Seconds a kernel serves registered schemas from memory before
+    /// reloading them, so that changes made through other kernels apply.
+    pub schema_refresh_secs: u64,
+}
+
+/// Cumulative weights at which a block counts as partially or fully confirmed.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmationThresholds {
+    pub partial: i64,
+    pub confirmed: i64,
+}
+
+impl ConfirmationThresholds {
+    pub fn classify(&self, cumulative_weight: i64) -> Confirmation {
+        if cumulative_weight >= self.confirmed {
+            Confirmation::Confirmed
+        } else if cumulative_weight >= self.partial {
+            Confirmation::Partial
+        } else {
+            Confirmation::Unconfirmed
+        }
+    }
+
+    /// The `[min, max)` cumulative weight range of a confirmation state.
+    pub fn weight_range(&self, state: Confirmation) -> (Option<i64>, Option<i64>) {
+        match state {
+            Confirmation::Unconfirmed => (None, Some(self.partial)),
+            Confirmation::Partial => (Some(self.partial), Some(self.confirmed)),
+            Confirmation::Confirmed => (Some(self.confirmed), None),
+        }
+    }
+}
+
+impl Default for TangleConfig {
+    fn default() -> Self {
+        Self {
+            quarantine_orphans: false,
+            pending_sweep_secs: 60,
+            strict_signatures: true,
+            allow_legacy_signatures: false,
+            content_addressed_ids: false,
+            tip_strategy: TipStrategy::Weighted,
+            confirmation: ConfirmationThresholds {
+                partial: 1,
+                confirmed: 5,
+            },
+            require_registered_keys: false,
+            peers: Vec::new(),
+            peer_api_key: None,
+            sync_interval_secs: 0,
+            checkpoint_interval_secs: 0,
+            audit_interval_secs: 10,
+            audit_batch_size: 500,
+            require_data_schema: false,
+            schema_refresh_secs: 30,
+        }
+    }
+}
+
+impl TangleConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            quarantine_orphans: env_flag("TANGLE_QUARANTINE_ORPHANS", defaults.quarantine_orphans),
+            pending_sweep_secs: env_i64(
+                "TANGLE_PENDING_SWEEP_SECS",
+                defaults.pending_sweep_secs as i64,
+            )
+            .max(0) as u64,
+            strict_signatures: env_flag("TANGLE_STRICT_SIGNATURES", defaults.strict_signatures),
+            allow_legacy_signatures: env_flag(
+                "TANGLE_ALLOW_LEGACY_SIGNATURES",
+                defaults.allow_legacy_signatures,
+            ),
+            content_addressed_ids: env_flag(
+                "TANGLE_CONTENT_ADDRESSED_IDS",
+                defaults.content_addressed_ids,
+            ),
+            tip_strategy: env::var("TANGLE_TIP_STRATEGY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.tip_strategy),
+            confirmation: ConfirmationThresholds {
+                partial: env_i64(
+                    "TANGLE_PARTIAL_CONFIRMATION_WEIGHT",
+                    defaults.confirmation.partial,
+                ),
+                confirmed: env_i64(
+                    "TANGLE_CONFIRMATION_WEIGHT",
+                    defaults.confirmation.confirmed,
+                ),
+            },
+            require_registered_keys: env_flag(
+                "TANGLE_REQUIRE_REGISTERED_KEYS",
+                defaults.require_registered_keys,
+            ),
+            peers: env::var("TANGLE_PEERS")
+                .map(|s| {
+                    s.split(',')
+                        .map(|p| p.trim().trim_end_matches('/').to_string())
+                        .filter(|p| !p.is_empty())
+                        .collect()
+                })
+                .unwrap_or(defaults.peers),
+            peer_api_key: env::var("TANGLE_PEER_API_KEY")
+                .ok()
+                .filter(|k| !k.is_empty()),
+            sync_interval_secs: env_i64(
+                "TANGLE_SYNC_INTERVAL_SECS",
+                defaults.sync_interval_secs as i64,
+            )
+            .max(0) as u64,
+            checkpoint_interval_secs: env_i64(
+                "TANGLE_CHECKPOINT_INTERVAL_SECS",
+                defaults.checkpoint_interval_secs as i64,
+            )
+            .max(0) as u64,
+            audit_interval_secs: env_i64(
+                "TANGLE_AUDIT_INTERVAL_SECS",
+                defaults.audit_interval_secs as i64,
+            )
+            .max(0) as u64,
+            audit_batch_size: env_i64("TANGLE_AUDIT_BATCH_SIZE", defaults.audit_batch_size).max(1),
+            require_data_schema: env_flag(
+                "TANGLE_REQUIRE_DATA_SCHEMA",
+                defaults.require_data_schema,
+            ),
+            schema_refresh_secs: env_i64(
+                "TANGLE_SCHEMA_REFRESH_SECS",
+                defaults.schema_refresh_secs as i64,
+            )
+            .max(0) as u64,
+        }
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|s| s == "true" || s == "1")
+        .unwrap_or(default)
+}
+
+fn env_i64(name: &str, default: i64) -> i64 {
+    env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default)
+}
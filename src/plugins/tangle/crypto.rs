@@ -1,4 +1,37 @@
 use ed25519_dalek::{PublicKey, Signature, Verifier};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// How the signed message of a block is derived from its contents.
+/// Stored as `tangle_blocks.sig_scheme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigScheme {
+    /// Signature over `serde_json::to_vec(&data)` only, as accepted before
+    /// canonical payloads existed.
+    Legacy = 0,
+    /// Signature over [`SigningPayload::canonical_bytes`].
+    CanonicalV1 = 1,
+}
+
+impl SigScheme {
+    pub fn as_i16(self) -> i16 {
+        self as i16
+    }
+}
+
+impl TryFrom<i16> for SigScheme {
+    type Error = i16;
+
+    fn try_from(v: i16) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(SigScheme::Legacy),
+            1 => Ok(SigScheme::CanonicalV1),
+            other => Err(other),
+        }
+    }
+}
 
 pub fn verify_ed25519_signature(
     public_key_bytes: &[u8],
@@ -12,3 +45,356 @@ pub fn verify_ed25519_signature(
         Err(e) => Err(e),
     }
 }
+
+/// Why a block signature was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyFailure {
+    MalformedPublicKey,
+    MalformedSignature,
+    Mismatch,
+}
+
+/// Like [`verify_ed25519_signature`], but tells a key or signature that cannot
+/// be decoded apart from a well-formed signature that does not match.
+pub fn check_ed25519_signature(
+    public_key_bytes: &[u8],
+    message: &[u8],
+    signature_bytes: &[u8],
+) -> Result<(), VerifyFailure> {
+    PublicKey::from_bytes(public_key_bytes).map_err(|_| VerifyFailure::MalformedPublicKey)?;
+    Signature::from_bytes(signature_bytes).map_err(|_| VerifyFailure::MalformedSignature)?;
+    match verify_ed25519_signature(public_key_bytes, message, signature_bytes) {
+        Ok(true) => Ok(()),
+        _ => Err(VerifyFailure::Mismatch),
+    }
+}
+
+/// Serializes `value` as compact JSON with object keys sorted bytewise at
+/// every level, so the same document always yields the same bytes.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String((*key).clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key.as_str()], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Everything a [`SigScheme::CanonicalV1`] signature covers.
+#[derive(Debug, Clone, Copy)]
+pub struct SigningPayload<'a> {
+    pub id: Uuid,
+    pub parents: &'a [Uuid],
+    pub data: &'a Value,
+    pub timestamp_ms: i64,
+    /// Block this one replaces; must also be one of `parents`.
+    pub supersedes: Option<Uuid>,
+    /// Marks `supersedes` as deleted rather than replaced.
+    pub tombstone: bool,
+}
+
+impl SigningPayload<'_> {
+    /// Canonical JSON of `{"v":1,"id":..,"parents":[..],"data":..,"timestamp_ms":..}`
+    /// with parents sorted, so a signature also pins the block id, its place in
+    /// the DAG and the signing time. `supersedes` and `tombstone` are only
+    /// part of the envelope when set, which keeps plain blocks unchanged.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut parents: Vec<String> = self.parents.iter().map(|p| p.to_string()).collect();
+        parents.sort();
+        let mut envelope = serde_json::json!({
+            "v": SigScheme::CanonicalV1.as_i16(),
+            "id": self.id.to_string(),
+            "parents": parents,
+            "data": self.data,
+            "timestamp_ms": self.timestamp_ms,
+        });
+        if let Some(supersedes) = self.supersedes {
+            envelope["supersedes"] = Value::String(supersedes.to_string());
+        }
+        if self.tombstone {
+            envelope["tombstone"] = Value::Bool(true);
+        }
+        canonical_json(&envelope).into_bytes()
+    }
+}
+
+/// The bytes a block signature is checked against for the given scheme.
+pub fn signing_message(scheme: SigScheme, payload: &SigningPayload) -> Vec<u8> {
+    match scheme {
+        SigScheme::Legacy => serde_json::to_vec(payload.data).unwrap_or_default(),
+        SigScheme::CanonicalV1 => payload.canonical_bytes(),
+    }
+}
+
+/// SHA-256 over the canonical JSON of everything that makes a block what it
+/// is except its id: `{"v":1,"parents":[..],"data":..,"public_key":..}` plus
+/// `timestamp_ms`, `supersedes` and `tombstone` when set. Parents are sorted,
+/// so any node can recompute the hash of a block it received.
+pub fn content_hash(
+    parents: &[Uuid],
+    data: &Value,
+    public_key: &str,
+    timestamp_ms: Option<i64>,
+    supersedes: Option<Uuid>,
+    tombstone: bool,
+) -> [u8; 32] {
+    let mut parents: Vec<String> = parents.iter().map(|p| p.to_string()).collect();
+    parents.sort();
+    let mut envelope = serde_json::json!({
+        "v": 1,
+        "parents": parents,
+        "data": data,
+        "public_key": public_key,
+    });
+    if let Some(timestamp_ms) = timestamp_ms {
+        envelope["timestamp_ms"] = Value::from(timestamp_ms);
+    }
+    if let Some(supersedes) = supersedes {
+        envelope["supersedes"] = Value::String(supersedes.to_string());
+    }
+    if tombstone {
+        envelope["tombstone"] = Value::Bool(true);
+    }
+    Sha256::digest(canonical_json(&envelope).as_bytes()).into()
+}
+
+/// The block id derived from a content hash: its first 16 bytes as a
+/// version 8 (custom) UUID.
+pub fn content_address(hash: &[u8; 32]) -> Uuid {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// The message a user signs with a key to prove they hold it when
+/// registering it: canonical JSON of
+/// `{"purpose":"tangle-key-registration","public_key":..,"user_id":..,"v":1}`.
+/// Binding the user id keeps one user's proof from being replayed by another.
+pub fn key_registration_message(user_id: Uuid, public_key: &str) -> Vec<u8> {
+    canonical_json(&serde_json::json!({
+        "v": 1,
+        "purpose": "tangle-key-registration",
+        "user_id": user_id.to_string(),
+        "public_key": public_key,
+    }))
+    .into_bytes()
+}
+
+/// Fields of the archived block (`GET /tangle/export` line) a Merkle leaf
+/// commits to. `created_at` and `cumulative_weight` are left out: the first is
+/// assigned per node, the second changes as blocks are approved.
+const LEAF_FIELDS: [&str; 9] = [
+    "id",
+    "parents",
+    "data",
+    "signature",
+    "public_key",
+    "sig_scheme",
+    "timestamp_ms",
+    "supersedes",
+    "tombstone",
+];
+
+/// Leaf hash of an archived block: `SHA-256(0x00 || canonical JSON of
+/// LEAF_FIELDS)`, missing fields counting as `null`. The prefixes keep a leaf
+/// from being passed off as an inner node.
+pub fn block_leaf_hash(archived: &Value) -> [u8; 32] {
+    let leaf: serde_json::Map<String, Value> = LEAF_FIELDS
+        .iter()
+        .map(|f| {
+            (
+                f.to_string(),
+                archived.get(*f).cloned().unwrap_or(Value::Null),
+            )
+        })
+        .collect();
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(canonical_json(&Value::Object(leaf)).as_bytes());
+    hasher.finalize().into()
+}
+
+fn merkle_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Every level of the tree, leaves first. A node without a sibling is
+/// carried up unchanged rather than paired with itself.
+fn merkle_levels(leaves: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.to_vec()];
+    while levels.last().is_some_and(|l| l.len() > 1) {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Root over `leaves` in order; the hash of nothing for an empty slice.
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match merkle_levels(leaves).last().and_then(|top| top.first()) {
+        Some(root) => *root,
+        None => Sha256::digest([]).into(),
+    }
+}
+
+/// Which side of the running hash a proof sibling goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub side: Side,
+    /// Hex-encoded sibling hash.
+    pub hash: String,
+}
+
+/// Proof that a block is one of the leaves under a checkpoint root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// Hex-encoded [`block_leaf_hash`].
+    pub leaf_hash: String,
+    pub path: Vec<ProofStep>,
+    /// Hex-encoded Merkle root the path leads to.
+    pub root: String,
+}
+
+/// Builds the inclusion proof for `leaves[index]`, or `None` when out of range.
+pub fn merkle_proof(leaves: &[[u8; 32]], index: usize) -> Option<InclusionProof> {
+    let leaf = leaves.get(index)?;
+    let levels = merkle_levels(leaves);
+    let mut path = Vec::new();
+    let mut i = index;
+    for level in &levels[..levels.len() - 1] {
+        let sibling = i ^ 1;
+        if let Some(hash) = level.get(sibling) {
+            path.push(ProofStep {
+                side: if sibling < i { Side::Left } else { Side::Right },
+                hash: hex::encode(hash),
+            });
+        }
+        i /= 2;
+    }
+    Some(InclusionProof {
+        leaf_index: index as u64,
+        leaf_count: leaves.len() as u64,
+        leaf_hash: hex::encode(leaf),
+        path,
+        root: hex::encode(merkle_root(leaves)),
+    })
+}
+
+/// Checks offline that `archived` (a block as exported by `GET
+/// /tangle/export`) is the leaf `proof` commits to and that the path leads
+/// to `proof.root`. The path must have the shape `leaf_index` and
+/// `leaf_count` dictate, so compare the root and `leaf_count` against a
+/// checkpoint you trust (its `block_count`).
+pub fn verify_inclusion(archived: &Value, proof: &InclusionProof) -> bool {
+    let leaf = block_leaf_hash(archived);
+    if hex::decode(&proof.leaf_hash).ok().as_deref() != Some(&leaf[..]) {
+        return false;
+    }
+    let Some(sides) = path_sides(proof.leaf_index, proof.leaf_count) else {
+        return false;
+    };
+    if !proof.path.iter().map(|step| step.side).eq(sides) {
+        return false;
+    }
+    let mut acc = leaf;
+    for step in &proof.path {
+        let Some(sibling) = decode_hash(&step.hash) else {
+            return false;
+        };
+        acc = match step.side {
+            Side::Left => merkle_node(&sibling, &acc),
+            Side::Right => merkle_node(&acc, &sibling),
+        };
+    }
+    decode_hash(&proof.root) == Some(acc)
+}
+
+/// The sides of the siblings on the path from leaf `index` of a tree of
+/// `count` leaves, as [`merkle_proof`] builds it.
+fn path_sides(index: u64, count: u64) -> Option<Vec<Side>> {
+    if index >= count {
+        return None;
+    }
+    let (mut i, mut width) = (index, count);
+    let mut sides = Vec::new();
+    while width > 1 {
+        let sibling = i ^ 1;
+        if sibling < width {
+            sides.push(if sibling < i { Side::Left } else { Side::Right });
+        }
+        i /= 2;
+        width = width.div_ceil(2);
+    }
+    Some(sides)
+}
+
+fn decode_hash(raw: &str) -> Option<[u8; 32]> {
+    hex::decode(raw).ok()?.try_into().ok()
+}
+
+/// Links a checkpoint to its predecessor: `SHA-256(0x02 || previous chain
+/// hash (zeros for the first) || root || from_seq || to_seq)`, integers as
+/// big-endian i64. Rewriting any earlier checkpoint changes every later one.
+pub fn checkpoint_chain_hash(
+    previous: Option<&[u8; 32]>,
+    root: &[u8; 32],
+    from_seq: i64,
+    to_seq: i64,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x02]);
+    hasher.update(previous.unwrap_or(&[0u8; 32]));
+    hasher.update(root);
+    hasher.update(from_seq.to_be_bytes());
+    hasher.update(to_seq.to_be_bytes());
+    hasher.finalize().into()
+}
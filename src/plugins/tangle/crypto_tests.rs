@@ -0,0 +1,186 @@
+use crate::plugins::tangle::crypto::{
+    SigScheme, SigningPayload, block_leaf_hash, canonical_json, checkpoint_chain_hash,
+    content_address, content_hash, merkle_proof, merkle_root, verify_ed25519_signature,
+    verify_inclusion,
+};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use uuid::Uuid;
+
+fn keypair() -> Keypair {
+    let secret = SecretKey::from_bytes(&[7u8; 32]).expect("secret key");
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+fn canonical_signing_bytes(
+    id: Uuid,
+    parents: &[Uuid],
+    data: &serde_json::Value,
+    timestamp_ms: i64,
+) -> Vec<u8> {
+    SigningPayload {
+        id,
+        parents,
+        data,
+        timestamp_ms,
+        supersedes: None,
+        tombstone: false,
+    }
+    .canonical_bytes()
+}
+
+#[test]
+fn canonical_json_sorts_keys_at_every_level() {
+    let a: serde_json::Value =
+        serde_json::from_str(r#"{"b":1,"a":{"z":[{"y":2,"x":1}],"c":"s"}}"#).unwrap();
+    assert_eq!(
+        canonical_json(&a),
+        r#"{"a":{"c":"s","z":[{"x":1,"y":2}]},"b":1}"#
+    );
+}
+
+#[test]
+fn canonical_signing_bytes_ignore_key_and_parent_order() {
+    let id = Uuid::new_v4();
+    let p1 = Uuid::new_v4();
+    let p2 = Uuid::new_v4();
+    let d1: serde_json::Value = serde_json::from_str(r#"{"t":"temp","v":21.5}"#).unwrap();
+    let d2: serde_json::Value = serde_json::from_str(r#"{"v":21.5,"t":"temp"}"#).unwrap();
+    assert_eq!(
+        canonical_signing_bytes(id, &[p1, p2], &d1, 1_700_000_000_000),
+        canonical_signing_bytes(id, &[p2, p1], &d2, 1_700_000_000_000)
+    );
+}
+
+#[test]
+fn canonical_signing_bytes_cover_id_parents_and_timestamp() {
+    let id = Uuid::new_v4();
+    let parent = Uuid::new_v4();
+    let data = serde_json::json!({"k": "v"});
+    let base = canonical_signing_bytes(id, &[parent], &data, 1);
+    assert_ne!(
+        base,
+        canonical_signing_bytes(Uuid::new_v4(), &[parent], &data, 1)
+    );
+    assert_ne!(
+        base,
+        canonical_signing_bytes(id, &[Uuid::new_v4()], &data, 1)
+    );
+    assert_ne!(base, canonical_signing_bytes(id, &[], &data, 1));
+    assert_ne!(base, canonical_signing_bytes(id, &[parent], &data, 2));
+}
+
+#[test]
+fn signature_over_canonical_bytes_verifies_only_for_same_block() {
+    let kp = keypair();
+    let id = Uuid::new_v4();
+    let parent = Uuid::new_v4();
+    let data = serde_json::json!({"reading": 3});
+    let msg = canonical_signing_bytes(id, &[parent], &data, 42);
+    let sig = kp.sign(&msg).to_bytes();
+    let pk = kp.public.to_bytes();
+
+    assert!(verify_ed25519_signature(&pk, &msg, &sig).unwrap());
+    let reparented = canonical_signing_bytes(id, &[Uuid::new_v4()], &data, 42);
+    assert!(verify_ed25519_signature(&pk, &reparented, &sig).is_err());
+}
+
+#[test]
+fn sig_scheme_round_trips_through_i16() {
+    for scheme in [SigScheme::Legacy, SigScheme::CanonicalV1] {
+        assert_eq!(SigScheme::try_from(scheme.as_i16()), Ok(scheme));
+    }
+    assert_eq!(SigScheme::try_from(9), Err(9));
+}
+
+#[test]
+fn content_hash_ignores_parent_order_but_not_author() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    let data = serde_json::json!({"x": 1, "y": [2, 3]});
+    let hash = content_hash(&[a, b], &data, "key-1", Some(5), None, false);
+
+    assert_eq!(
+        hash,
+        content_hash(&[b, a], &data, "key-1", Some(5), None, false)
+    );
+    assert_ne!(
+        hash,
+        content_hash(&[a, b], &data, "key-2", Some(5), None, false)
+    );
+    assert_ne!(
+        hash,
+        content_hash(&[a, b], &data, "key-1", Some(6), None, false)
+    );
+    assert_ne!(
+        hash,
+        content_hash(&[a, b], &data, "key-1", Some(5), Some(a), false)
+    );
+}
+
+#[test]
+fn content_address_is_a_stable_v8_uuid() {
+    let hash = content_hash(&[], &serde_json::json!("x"), "key", None, None, false);
+    let id = content_address(&hash);
+    assert_eq!(id.get_version_num(), 8);
+    assert_eq!(id, content_address(&hash));
+    assert_eq!(&id.as_bytes()[..6], &hash[..6]);
+}
+
+#[test]
+fn merkle_proofs_verify_for_every_leaf() {
+    for count in 1..=9u64 {
+        let blocks: Vec<serde_json::Value> = (0..count)
+            .map(|n| serde_json::json!({"id": Uuid::new_v4(), "parents": [], "data": {"n": n}}))
+            .collect();
+        let leaves: Vec<[u8; 32]> = blocks.iter().map(block_leaf_hash).collect();
+        let root = hex::encode(merkle_root(&leaves));
+        for (i, block) in blocks.iter().enumerate() {
+            let proof = merkle_proof(&leaves, i).unwrap();
+            assert_eq!(proof.root, root);
+            assert!(verify_inclusion(block, &proof), "leaf {} of {}", i, count);
+        }
+        assert!(merkle_proof(&leaves, count as usize).is_none());
+    }
+}
+
+#[test]
+fn merkle_proof_rejects_altered_block_or_path() {
+    let blocks: Vec<serde_json::Value> = (0..5)
+        .map(|n| serde_json::json!({"id": Uuid::new_v4(), "data": {"n": n}, "tombstone": false}))
+        .collect();
+    let leaves: Vec<[u8; 32]> = blocks.iter().map(block_leaf_hash).collect();
+    let proof = merkle_proof(&leaves, 2).unwrap();
+
+    let mut altered = blocks[2].clone();
+    altered["data"]["n"] = serde_json::json!(99);
+    assert!(!verify_inclusion(&altered, &proof));
+
+    // fields outside the leaf do not matter
+    let mut annotated = blocks[2].clone();
+    annotated["cumulative_weight"] = serde_json::json!(12);
+    assert!(verify_inclusion(&annotated, &proof));
+
+    let mut swapped = proof.clone();
+    swapped.path.swap(0, 1);
+    assert!(!verify_inclusion(&blocks[2], &swapped));
+
+    // the position claimed must be the one the path walks
+    let mut moved = proof.clone();
+    moved.leaf_index = 3;
+    assert!(!verify_inclusion(&blocks[2], &moved));
+    let mut shrunk = proof.clone();
+    shrunk.leaf_count = 4;
+    assert!(!verify_inclusion(&blocks[2], &shrunk));
+    let mut outside = proof.clone();
+    outside.leaf_index = 5;
+    assert!(!verify_inclusion(&blocks[2], &outside));
+}
+
+#[test]
+fn checkpoint_chain_hash_depends_on_predecessor() {
+    let root = [3u8; 32];
+    let first = checkpoint_chain_hash(None, &root, 0, 10);
+    assert_ne!(first, checkpoint_chain_hash(Some(&[1u8; 32]), &root, 0, 10));
+    assert_ne!(first, checkpoint_chain_hash(None, &root, 0, 11));
+}
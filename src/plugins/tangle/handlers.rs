@@ -1,141 +1,714 @@
 use crate::http_error::AppError;
-use crate::plugins::tangle::crypto::verify_ed25519_signature;
-use crate::plugins::tangle::models::{TangleBlockCreate, TangleBlockDto, TangleBlockUpdate};
-use crate::plugins::tangle::repo;
-use axum::http::StatusCode;
+use crate::plugins::auth::handlers::AuthUser;
+use crate::plugins::communication::shared::{Cursor, ListResponse, PageRequest};
+use crate::plugins::tangle::audit::{FindingKind, TangleAuditor};
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::models::{
+    AuditFindingRow, AuditReport, BlockFetch, BlockFilter, BlockRef, CheckpointDto, Confirmation,
+    DataSchemaRow, DataSchemaUpsert, SigningKeyCreate, SigningKeyDto, TangleBatchItem,
+    TangleBatchResponse, TangleBlockCreate, TangleBlockDto, TangleImportRejection,
+    TangleImportReport, TangleInclusionProof, TangleSyncReport, TipExchange, TipExchangeResponse,
+};
+use crate::plugins::tangle::schema::SchemaRegistry;
+use crate::plugins::tangle::service::{self, BatchMode, Submission};
+use crate::plugins::tangle::stream::TangleStream;
+use crate::plugins::tangle::sync::{self, TangleSync};
+use crate::plugins::tangle::tips::{self, TipStrategy};
+use crate::plugins::tangle::{checkpoint, repo};
+use axum::body::Body;
+use axum::http::{StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json, extract::Path};
-use base64::Engine as _;
-use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use futures_util::StreamExt;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 pub async fn create_block(
     Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+    auth: Option<AuthUser>,
     Json(payload): Json<TangleBlockCreate>,
-) -> Result<Json<TangleBlockDto>, AppError> {
-    
-    let sig_bytes = BASE64_ENGINE.decode(&payload.signature).map_err(|e| {
-        AppError::new(
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalidSignatureEncoding: {}", e),
+) -> Result<(StatusCode, Json<TangleBlockDto>), AppError> {
+    let block = service::prepare_block(&config, payload)?;
+    service::check_submission(&pool, &config, &schemas, auth.as_ref(), &block).await?;
+
+    match service::submit_block(&pool, &config, block).await? {
+        Submission::Stored(row) | Submission::Existing(row) => Ok((
+            StatusCode::OK,
+            Json(TangleBlockDto::from_row(row, &config.confirmation)),
+        )),
+        Submission::Pending(row) => Ok((
+            StatusCode::ACCEPTED,
+            Json(TangleBlockDto::from_row(row, &config.confirmation)),
+        )),
+    }
+}
+
+const MAX_BATCH: usize = 1000;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct BatchQuery {
+    pub mode: Option<String>,
+}
+
+/// Submits up to [`MAX_BATCH`] blocks at once. Blocks may name other blocks
+/// of the same batch as parents. In `atomic` mode (the default) the first
+/// failing block aborts the whole batch; in `partial` mode every block gets
+/// its own result.
+pub async fn create_blocks_batch(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+    auth: Option<AuthUser>,
+    axum::extract::Query(q): axum::extract::Query<BatchQuery>,
+    Json(payload): Json<Vec<TangleBlockCreate>>,
+) -> Result<Json<TangleBatchResponse>, AppError> {
+    let mode = match q.mode.as_deref() {
+        None => BatchMode::Atomic,
+        Some(raw) => raw.parse().map_err(|_| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("invalidBatchMode: {}", raw),
+            )
+            .with_code("invalid_batch_mode")
+        })?,
+    };
+    if payload.is_empty() {
+        return Err(AppError::new(StatusCode::BAD_REQUEST, "emptyBatch").with_code("empty_batch"));
+    }
+    if payload.len() > MAX_BATCH {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("batchTooLarge: max {}", MAX_BATCH),
         )
-    })?;
-    
-    if let Ok(pk_bytes) = BASE64_ENGINE.decode(&payload.public_key) {
-        if pk_bytes.len() == 32 {
-            
-            let msg = serde_json::to_vec(&payload.data).map_err(|e| {
-                AppError::new(
-                    axum::http::StatusCode::BAD_REQUEST,
-                    format!("invalidDataPayload: {}", e),
-                )
-            })?;
-            match verify_ed25519_signature(&pk_bytes, &msg, &sig_bytes) {
-                Ok(true) => {
-                    
-                }
-                Ok(false) => {
-                    return Err(AppError::new(
-                        axum::http::StatusCode::BAD_REQUEST,
-                        "signatureVerificationFailed".to_string(),
-                    ));
+        .with_code("batch_too_large"));
+    }
+
+    let mut prepared = service::prepare_batch(config.clone(), payload).await;
+    for item in prepared.iter_mut() {
+        if let Ok(block) = item
+            && let Err(e) =
+                service::check_submission(&pool, &config, &schemas, auth.as_ref(), block).await
+        {
+            *item = Err(e);
+        }
+    }
+
+    let outcomes = service::submit_batch(&pool, &config, prepared, mode).await?;
+    let mut response = TangleBatchResponse {
+        stored: 0,
+        existing: 0,
+        pending: 0,
+        rejected: 0,
+        results: Vec::with_capacity(outcomes.len()),
+    };
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        let item = match outcome {
+            Ok(submission) => {
+                let (status, row) = match submission {
+                    Submission::Stored(row) => {
+                        response.stored += 1;
+                        (StatusCode::OK, row)
+                    }
+                    Submission::Existing(row) => {
+                        response.existing += 1;
+                        (StatusCode::OK, row)
+                    }
+                    Submission::Pending(row) => {
+                        response.pending += 1;
+                        (StatusCode::ACCEPTED, row)
+                    }
+                };
+                TangleBatchItem {
+                    index,
+                    status: status.as_u16(),
+                    block: Some(TangleBlockDto::from_row(row, &config.confirmation)),
+                    error: None,
+                    code: None,
+                    details: None,
                 }
-                Err(_) => {
-                    return Err(AppError::new(
-                        axum::http::StatusCode::BAD_REQUEST,
-                        "signatureVerificationError".to_string(),
-                    ));
+            }
+            Err(e) => {
+                response.rejected += 1;
+                TangleBatchItem {
+                    index,
+                    status: e.status.as_u16(),
+                    block: None,
+                    error: Some(e.message),
+                    code: e.code,
+                    details: e.details,
                 }
             }
-        }
+        };
+        response.results.push(item);
     }
-    
-    let row = repo::insert_block(
-        &pool,
-        payload.id,
-        &payload.parents,
-        &payload.data,
-        &sig_bytes,
-        &payload.public_key,
-    )
-    .await?;
-    Ok(Json(row.into()))
+    Ok(Json(response))
+}
+
+/// Answers a peer's tip exchange with our tips and the peer tips we lack.
+pub async fn exchange_tips(
+    Extension(pool): Extension<PgPool>,
+    Json(payload): Json<TipExchange>,
+) -> Result<Json<TipExchangeResponse>, AppError> {
+    let tips = repo::tips(&pool, sync::TIP_EXCHANGE_LIMIT).await?;
+    let unknown = repo::find_missing_parents(&pool, &payload.tips).await?;
+    Ok(Json(TipExchangeResponse {
+        tips: tips.into_iter().map(|t| t.id).collect(),
+        unknown,
+    }))
 }
 
+/// Returns the requested blocks in archive form, up to
+/// [`sync::FETCH_BATCH`] per call. Ids this node does not hold are left out.
+pub async fn fetch_blocks(
+    Extension(pool): Extension<PgPool>,
+    Json(payload): Json<BlockFetch>,
+) -> Result<Json<Vec<TangleBlockCreate>>, AppError> {
+    if payload.ids.len() > sync::FETCH_BATCH {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("batchTooLarge: max {}", sync::FETCH_BATCH),
+        )
+        .with_code("batch_too_large"));
+    }
+    let rows = repo::find_blocks(&pool, &payload.ids).await?;
+    Ok(Json(
+        rows.into_iter().map(TangleBlockCreate::from).collect(),
+    ))
+}
+
+/// Pulls from every configured peer now.
+pub async fn sync_now(Extension(sync): Extension<Arc<TangleSync>>) -> Json<Vec<TangleSyncReport>> {
+    Json(sync.sync_all().await)
+}
+
+/// Looks a block up by UUID or by its hex-encoded content hash.
 pub async fn get_block(
     Extension(pool): Extension<PgPool>,
-    Path(id): Path<uuid::Uuid>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Path(block_ref): Path<String>,
 ) -> Result<Json<TangleBlockDto>, AppError> {
-    let row = repo::get_block(&pool, id).await?;
-    Ok(Json(row.into()))
+    let row = match block_ref.parse::<BlockRef>() {
+        Ok(BlockRef::Id(id)) => repo::get_block(&pool, id).await?,
+        Ok(BlockRef::Hash(hash)) => repo::find_block_by_hash(&pool, &hash)
+            .await?
+            .ok_or_else(block_not_found)?,
+        Err(()) => {
+            return Err(AppError::new(StatusCode::BAD_REQUEST, "invalidBlockRef")
+                .with_code("invalid_block_ref"));
+        }
+    };
+    Ok(Json(TangleBlockDto::from_row(row, &config.confirmation)))
 }
 
 #[derive(Debug, serde::Deserialize)]
 pub struct ListQuery {
     pub page: Option<u32>,
     pub per_page: Option<u32>,
+    /// `next_cursor` of a previous page; excludes `page`.
+    pub cursor: Option<String>,
+    /// Include `total` and `total_pages`; defaults to true without a cursor.
+    pub count: Option<bool>,
+    pub confirmation: Option<String>,
+    /// JSON document `data` must contain, e.g. `{"sensor":{"type":"co2"}}`.
+    pub contains: Option<String>,
+    /// Dot-separated path into `data`, compared against `equals`.
+    pub path: Option<String>,
+    /// JSON literal expected at `path`; anything that is not valid JSON is
+    /// compared as a string.
+    pub equals: Option<String>,
+    pub public_key: Option<String>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ListQuery {
+    fn filter(&self, config: &TangleConfig) -> Result<BlockFilter, AppError> {
+        let (min_weight, max_weight) = match self.confirmation.as_deref() {
+            None => (None, None),
+            Some(raw) => raw
+                .parse::<Confirmation>()
+                .map(|c| config.confirmation.weight_range(c))
+                .map_err(|_| {
+                    AppError::new(
+                        StatusCode::BAD_REQUEST,
+                        format!("unknownConfirmation: {}", raw),
+                    )
+                    .with_code("invalid_confirmation")
+                })?,
+        };
+        let payload = payload_filter(
+            self.contains.as_deref(),
+            self.path.as_deref(),
+            self.equals.as_deref(),
+        )?;
+        Ok(BlockFilter {
+            min_weight,
+            max_weight,
+            public_key: self.public_key.clone(),
+            created_after: self.created_after,
+            created_before: self.created_before,
+            ..payload
+        })
+    }
+}
+
+/// Parses the `contains` and `path`/`equals` parameters into a filter.
+fn payload_filter(
+    contains: Option<&str>,
+    path: Option<&str>,
+    equals: Option<&str>,
+) -> Result<BlockFilter, AppError> {
+    let contains = match contains {
+        None => None,
+        Some(raw) => Some(serde_json::from_str(raw).map_err(|e| {
+            AppError::new(StatusCode::BAD_REQUEST, format!("invalidContains: {}", e))
+                .with_code("invalid_filter")
+        })?),
+    };
+    let (path, path_value) = match (path, equals) {
+        (None, None) => (None, None),
+        (Some(path), Some(equals)) if !path.is_empty() => (
+            Some(path.split('.').map(str::to_string).collect()),
+            Some(
+                serde_json::from_str(equals)
+                    .unwrap_or_else(|_| serde_json::Value::String(equals.to_string())),
+            ),
+        ),
+        _ => {
+            return Err(
+                AppError::new(StatusCode::BAD_REQUEST, "pathAndEqualsGoTogether")
+                    .with_code("invalid_filter"),
+            );
+        }
+    };
+    Ok(BlockFilter {
+        contains,
+        path,
+        path_value,
+        ..BlockFilter::default()
+    })
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StreamQuery {
+    pub public_key: Option<String>,
+    pub contains: Option<String>,
+    pub path: Option<String>,
+    pub equals: Option<String>,
+    /// Same as the `Last-Event-ID` header, for clients that cannot set it.
+    pub last_event_id: Option<i64>,
+}
+
+/// Server-sent events for every newly stored block (`event: block`, `id:`
+/// the block's seq). Reconnecting with `Last-Event-ID` replays what was
+/// missed.
+pub async fn stream_blocks(
+    Extension(stream): Extension<Arc<TangleStream>>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Query(q): axum::extract::Query<StreamQuery>,
+) -> Result<Sse<impl futures_util::Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    let filter = BlockFilter {
+        public_key: q.public_key,
+        ..payload_filter(
+            q.contains.as_deref(),
+            q.path.as_deref(),
+            q.equals.as_deref(),
+        )?
+    };
+    let last_event_id = match headers.get("last-event-id") {
+        None => q.last_event_id,
+        Some(raw) => Some(
+            raw.to_str()
+                .ok()
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .ok_or_else(|| {
+                    AppError::new(StatusCode::BAD_REQUEST, "invalidLastEventId")
+                        .with_code("invalid_last_event_id")
+                })?,
+        ),
+    };
+    let events = stream.subscribe(config, filter, last_event_id).await?;
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
 }
 
 pub async fn list_blocks(
     Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
     axum::extract::Query(q): axum::extract::Query<ListQuery>,
-) -> Result<Json<crate::plugins::communication::shared::ListResponse<TangleBlockDto>>, AppError> {
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) * per_page) as i64;
-
-    let (rows, total) = repo::list_blocks(&pool, per_page as i64, offset).await?;
-    let items: Vec<TangleBlockDto> = rows.into_iter().map(|r| r.into()).collect();
-
-    let fetched = items.len() as i64;
-    let has_more = offset + fetched < total;
-    let total_pages = if total == 0 {
-        0
-    } else {
-        ((total as f64) / (per_page as f64)).ceil() as i64
-    };
-    let resp = crate::plugins::communication::shared::ListResponse {
-        items,
-        page,
-        per_page,
-        total,
-        total_pages,
-        has_more,
-    };
+) -> Result<Json<ListResponse<TangleBlockDto>>, AppError> {
+    let req = PageRequest::new(q.page, q.per_page, q.cursor.as_deref(), q.count)?;
+    let filter = q.filter(&config)?;
+
+    let (rows, total) = repo::list_blocks(&pool, &req, &filter).await?;
+    let resp = ListResponse::from_page(rows, &req, total, |r| Cursor {
+        created_at: r.created_at,
+        id: r.id,
+    })
+    .map(|r| TangleBlockDto::from_row(r, &config.confirmation));
     Ok(Json(resp))
 }
 
-pub async fn update_block(
+/// Resolves a block to the latest version of its supersession chain.
+pub async fn get_effective_block(
     Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
     Path(id): Path<uuid::Uuid>,
-    Json(payload): Json<TangleBlockUpdate>,
 ) -> Result<Json<TangleBlockDto>, AppError> {
-    
-    let sig_bytes_opt: Option<Vec<u8>> = match &payload.signature {
-        Some(s) => Some(BASE64_ENGINE.decode(s).map_err(|e| {
+    let chain = repo::supersession_chain(&pool, id).await?;
+    let head = chain.into_iter().last().ok_or_else(block_not_found)?;
+    if head.tombstone {
+        return Err(AppError::new(StatusCode::GONE, "blockDeleted").with_code("block_deleted"));
+    }
+    Ok(Json(TangleBlockDto::from_row(head, &config.confirmation)))
+}
+
+pub async fn get_block_history(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<TangleBlockDto>>, AppError> {
+    let chain = repo::supersession_chain(&pool, id).await?;
+    if chain.is_empty() {
+        return Err(block_not_found());
+    }
+    Ok(Json(
+        chain
+            .into_iter()
+            .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+            .collect(),
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TraversalQuery {
+    pub depth: Option<u32>,
+}
+
+pub async fn get_children(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<TangleBlockDto>>, AppError> {
+    repo::get_block(&pool, id).await?;
+    let rows = repo::children(&pool, id).await?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+            .collect(),
+    ))
+}
+
+pub async fn get_ancestors(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Path(id): Path<uuid::Uuid>,
+    axum::extract::Query(q): axum::extract::Query<TraversalQuery>,
+) -> Result<Json<Vec<TangleBlockDto>>, AppError> {
+    repo::get_block(&pool, id).await?;
+    let depth = q.depth.unwrap_or(10).clamp(1, 100);
+    let rows = repo::ancestors(&pool, id, depth as i32).await?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+            .collect(),
+    ))
+}
+
+pub async fn get_descendants(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Path(id): Path<uuid::Uuid>,
+    axum::extract::Query(q): axum::extract::Query<TraversalQuery>,
+) -> Result<Json<Vec<TangleBlockDto>>, AppError> {
+    repo::get_block(&pool, id).await?;
+    let depth = q.depth.unwrap_or(10).clamp(1, 100);
+    let rows = repo::descendants(&pool, id, depth as i32).await?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+            .collect(),
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TipsQuery {
+    pub limit: Option<u32>,
+}
+
+pub async fn list_tips(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    axum::extract::Query(q): axum::extract::Query<TipsQuery>,
+) -> Result<Json<Vec<TangleBlockDto>>, AppError> {
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    let rows = repo::tips(&pool, limit as i64).await?;
+    Ok(Json(
+        rows.into_iter()
+            .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+            .collect(),
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TipSelectQuery {
+    pub count: Option<u32>,
+    pub strategy: Option<String>,
+}
+
+/// Recommends parents for a new block.
+pub async fn select_tips(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    axum::extract::Query(q): axum::extract::Query<TipSelectQuery>,
+) -> Result<Json<ListResponse<TangleBlockDto>>, AppError> {
+    let strategy = match q.strategy.as_deref() {
+        None => config.tip_strategy,
+        Some(raw) => raw.parse::<TipStrategy>().map_err(|_| {
             AppError::new(
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("invalidSignatureEncoding: {}", e),
+                StatusCode::BAD_REQUEST,
+                format!("unknownTipStrategy: {}", raw),
             )
-        })?),
-        None => None,
+            .with_code("invalid_tip_strategy")
+        })?,
+    };
+    let count = q.count.unwrap_or(2).clamp(1, 8);
+
+    let rows = tips::select_tips(&pool, strategy, count as usize).await?;
+    let items: Vec<TangleBlockDto> = rows
+        .into_iter()
+        .map(|r| TangleBlockDto::from_row(r, &config.confirmation))
+        .collect();
+    let resp = ListResponse {
+        total: Some(items.len() as i64),
+        items,
+        page: Some(1),
+        per_page: count,
+        total_pages: Some(1),
+        has_more: false,
+        next_cursor: None,
     };
+    Ok(Json(resp))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CheckpointListQuery {
+    pub limit: Option<i64>,
+}
+
+/// Most recent checkpoints first.
+pub async fn list_checkpoints(
+    Extension(pool): Extension<PgPool>,
+    axum::extract::Query(q): axum::extract::Query<CheckpointListQuery>,
+) -> Result<Json<Vec<CheckpointDto>>, AppError> {
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    let rows = repo::list_checkpoints(&pool, limit).await?;
+    Ok(Json(rows.into_iter().map(CheckpointDto::from).collect()))
+}
+
+/// Checkpoints the blocks stored since the last checkpoint now; 204 when
+/// there are none.
+pub async fn create_checkpoint(Extension(pool): Extension<PgPool>) -> Result<Response, AppError> {
+    match checkpoint::create_checkpoint(&pool).await? {
+        Some(row) => Ok((StatusCode::CREATED, Json(CheckpointDto::from(row))).into_response()),
+        None => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+pub async fn get_block_proof(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<TangleInclusionProof>, AppError> {
+    Ok(Json(checkpoint::inclusion_proof(&pool, id).await?))
+}
 
-    let row = repo::update_block(
+const EXPORT_BATCH: i64 = 500;
+
+/// Streams every stored block as NDJSON in topological order, one
+/// `POST /tangle/blocks` body per line.
+pub async fn export_blocks(Extension(pool): Extension<PgPool>) -> Response {
+    let lines = futures_util::stream::try_unfold(0i64, move |after_seq| {
+        let pool = pool.clone();
+        async move {
+            let rows = repo::export_batch(&pool, after_seq, EXPORT_BATCH)
+                .await
+                .map_err(|e| std::io::Error::other(e.message))?;
+            let Some(last) = rows.last().map(|r| r.seq) else {
+                return Ok(None);
+            };
+            let mut chunk = String::new();
+            for row in rows {
+                chunk.push_str(&serde_json::to_string(&TangleBlockCreate::from(row.block))?);
+                chunk.push('\n');
+            }
+            Ok::<_, std::io::Error>(Some((chunk, last)))
+        }
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+/// Ingests an NDJSON archive produced by [`export_blocks`]. Every line goes
+/// through the same checks as `POST /tangle/blocks`; blocks already present
+/// are skipped and failures are reported per line instead of aborting.
+pub async fn import_blocks(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<TangleConfig>>,
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+    body: Body,
+) -> Result<Json<TangleImportReport>, AppError> {
+    let mut report = TangleImportReport::default();
+    let mut chunks = body.into_data_stream();
+    let mut buf: Vec<u8> = Vec::new();
+    let mut line_no = 0u64;
+    while let Some(chunk) = chunks.next().await {
+        let bytes = chunk.map_err(|e| {
+            AppError::new(StatusCode::BAD_REQUEST, e.to_string()).with_code("invalid_body")
+        })?;
+        buf.extend_from_slice(&bytes);
+        while let Some(end) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            line_no += 1;
+            import_line(&pool, &config, &schemas, &mut report, line_no, &line[..end]).await;
+        }
+    }
+    if !buf.is_empty() {
+        import_line(&pool, &config, &schemas, &mut report, line_no + 1, &buf).await;
+    }
+    Ok(Json(report))
+}
+
+async fn import_line(
+    pool: &PgPool,
+    config: &TangleConfig,
+    schemas: &SchemaRegistry,
+    report: &mut TangleImportReport,
+    line_no: u64,
+    line: &[u8],
+) {
+    if line.iter().all(|b| b.is_ascii_whitespace()) {
+        return;
+    }
+    let payload: TangleBlockCreate = match serde_json::from_slice(line) {
+        Ok(p) => p,
+        Err(e) => {
+            report.rejected.push(TangleImportRejection {
+                line: Some(line_no),
+                id: None,
+                error: e.to_string(),
+                code: Some("invalid_json".to_string()),
+            });
+            return;
+        }
+    };
+    let id = payload.id;
+    let outcome = service::ingest_block(pool, config, schemas, payload).await;
+    service::record_ingest(report, Some(line_no), id, outcome);
+}
+
+/// Registers an ed25519 key for the caller after checking its proof of possession.
+pub async fn register_key(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
+    Json(payload): Json<SigningKeyCreate>,
+) -> Result<(StatusCode, Json<SigningKeyDto>), AppError> {
+    service::verify_key_possession(auth.user_id, &payload.public_key, &payload.proof)?;
+    let key = repo::insert_signing_key(
         &pool,
-        id,
-        payload.parents,
-        payload.data,
-        sig_bytes_opt,
-        payload.public_key,
+        auth.user_id,
+        &payload.public_key,
+        payload.label.as_deref(),
     )
     .await?;
-    Ok(Json(row.into()))
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+pub async fn list_keys(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
+) -> Result<Json<Vec<SigningKeyDto>>, AppError> {
+    Ok(Json(repo::list_signing_keys(&pool, auth.user_id).await?))
 }
 
-pub async fn delete_block(
+pub async fn revoke_key(
     Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
     Path(id): Path<uuid::Uuid>,
 ) -> Result<StatusCode, AppError> {
-    repo::delete_block(&pool, id).await?;
+    if !repo::revoke_signing_key(&pool, auth.user_id, id).await? {
+        return Err(
+            AppError::new(StatusCode::NOT_FOUND, "signingKeyNotFound").with_code("not_found")
+        );
+    }
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Audits every stored block now instead of waiting for the background loop.
+pub async fn run_audit(
+    Extension(auditor): Extension<Arc<TangleAuditor>>,
+) -> Result<Json<AuditReport>, AppError> {
+    Ok(Json(auditor.run_pass().await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AuditFindingsQuery {
+    pub kind: Option<String>,
+    /// Include findings that have since been resolved.
+    #[serde(default)]
+    pub all: bool,
+    pub limit: Option<i64>,
+}
+
+/// Most recently seen findings first; open ones only unless `all` is set.
+pub async fn list_audit_findings(
+    Extension(pool): Extension<PgPool>,
+    axum::extract::Query(q): axum::extract::Query<AuditFindingsQuery>,
+) -> Result<Json<Vec<AuditFindingRow>>, AppError> {
+    let kind = match q.kind.as_deref() {
+        Some(k) => Some(k.parse::<FindingKind>().map_err(|_| {
+            AppError::new(StatusCode::BAD_REQUEST, "invalidFindingKind")
+                .with_code("invalid_finding_kind")
+        })?),
+        None => None,
+    };
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    let rows =
+        repo::list_audit_findings(&pool, kind.map(FindingKind::as_str), q.all, limit).await?;
+    Ok(Json(rows))
+}
+
+pub async fn list_schemas(
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+) -> Result<Json<Vec<DataSchemaRow>>, AppError> {
+    Ok(Json(schemas.list().await?))
+}
+
+/// Registers or replaces the schema that block `data` of this `type` must satisfy.
+pub async fn put_schema(
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+    Path(data_type): Path<String>,
+    Json(payload): Json<DataSchemaUpsert>,
+) -> Result<Json<DataSchemaRow>, AppError> {
+    let row = schemas
+        .register(&data_type, payload.schema, payload.description.as_deref())
+        .await?;
+    Ok(Json(row))
+}
+
+pub async fn delete_schema(
+    Extension(schemas): Extension<Arc<SchemaRegistry>>,
+    Path(data_type): Path<String>,
+) -> Result<StatusCode, AppError> {
+    schemas.remove(&data_type).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn block_not_found() -> AppError {
+    AppError::new(StatusCode::NOT_FOUND, "notFound").with_code("not_found")
+}
@@ -1,5 +1,18 @@
+pub mod audit;
+pub mod checkpoint;
+pub mod config;
 pub mod crypto;
 pub mod handlers;
 pub mod models;
 pub mod plugin;
 pub mod repo;
+pub mod schema;
+pub mod service;
+pub mod stream;
+pub mod sync;
+pub mod tips;
+
+#[cfg(test)]
+mod crypto_tests;
+#[cfg(test)]
+mod tests;
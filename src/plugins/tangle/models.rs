@@ -1,7 +1,8 @@
+use crate::plugins::tangle::config::ConfirmationThresholds;
+use crate::plugins::tangle::crypto::{InclusionProof, SigScheme};
 use base64::Engine as _;
 use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TangleBlockCreate {
@@ -10,45 +11,357 @@ pub struct TangleBlockCreate {
     pub data: serde_json::Value,
     pub signature: String,
     pub public_key: String,
+    pub sig_scheme: Option<i16>,
+    pub timestamp_ms: Option<i64>,
+    pub supersedes: Option<uuid::Uuid>,
+    #[serde(default)]
+    pub tombstone: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TangleBlockUpdate {
-    pub parents: Option<Vec<String>>,
-    pub data: Option<serde_json::Value>,
-    pub signature: Option<String>,
-    pub public_key: Option<String>,
+impl From<TangleBlockRow> for TangleBlockCreate {
+    /// The archive form of a stored block, accepted as-is by `POST /tangle/import`.
+    fn from(r: TangleBlockRow) -> Self {
+        TangleBlockCreate {
+            id: Some(r.id),
+            parents: r.parents,
+            data: r.data,
+            signature: BASE64_ENGINE.encode(&r.signature),
+            public_key: r.public_key,
+            sig_scheme: Some(r.sig_scheme),
+            timestamp_ms: r.timestamp_ms,
+            supersedes: r.supersedes,
+            tombstone: r.tombstone,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// A block that passed request validation, ready to be stored or quarantined.
+#[derive(Debug, Clone)]
+pub struct NewBlock {
+    pub id: uuid::Uuid,
+    pub parents: Vec<uuid::Uuid>,
+    pub data: serde_json::Value,
+    pub signature: Vec<u8>,
+    pub public_key: String,
+    pub sig_scheme: SigScheme,
+    pub timestamp_ms: Option<i64>,
+    pub supersedes: Option<uuid::Uuid>,
+    pub tombstone: bool,
+    /// SHA-256 content hash, see [`crate::plugins::tangle::crypto::content_hash`].
+    pub hash: [u8; 32],
+}
+
+impl NewBlock {
+    pub fn parent_strings(&self) -> Vec<String> {
+        self.parents.iter().map(|p| p.to_string()).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TangleBlockDto {
     pub id: uuid::Uuid,
     pub parents: Vec<String>,
     pub data: serde_json::Value,
     pub signature: String,
     pub public_key: String,
+    pub sig_scheme: i16,
+    pub timestamp_ms: Option<i64>,
+    pub supersedes: Option<uuid::Uuid>,
+    pub tombstone: bool,
+    /// Hex-encoded content hash; absent on blocks stored before hashes existed.
+    pub hash: Option<String>,
+    /// Number of blocks that directly or indirectly approve this one. Stops
+    /// growing once the block is confirmed, so it is a lower bound then.
+    pub cumulative_weight: i64,
+    pub confirmation: Confirmation,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, sqlx::FromRow)]
+/// Confirmation state derived from a block's cumulative weight, see
+/// [`crate::plugins::tangle::config::ConfirmationThresholds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confirmation {
+    Unconfirmed,
+    Partial,
+    Confirmed,
+}
+
+impl std::str::FromStr for Confirmation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unconfirmed" => Ok(Confirmation::Unconfirmed),
+            "partial" => Ok(Confirmation::Partial),
+            "confirmed" => Ok(Confirmation::Confirmed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Filters of `GET /tangle/blocks`; `None` fields do not restrict the result.
+#[derive(Debug, Default)]
+pub struct BlockFilter {
+    /// `[min, max)` cumulative weight range, see `ConfirmationThresholds::weight_range`.
+    pub min_weight: Option<i64>,
+    pub max_weight: Option<i64>,
+    /// `data` must contain this JSON document (`@>`).
+    pub contains: Option<serde_json::Value>,
+    /// `data` must hold `path_value` at this path.
+    pub path: Option<Vec<String>>,
+    pub path_value: Option<serde_json::Value>,
+    pub public_key: Option<String>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct TangleBlockRow {
     pub id: uuid::Uuid,
     pub parents: Vec<String>,
     pub data: serde_json::Value,
     pub signature: Vec<u8>,
     pub public_key: String,
+    pub sig_scheme: i16,
+    pub timestamp_ms: Option<i64>,
+    pub supersedes: Option<uuid::Uuid>,
+    pub tombstone: bool,
+    pub hash: Option<Vec<u8>>,
+    pub cumulative_weight: i64,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<TangleBlockRow> for TangleBlockDto {
-    fn from(r: TangleBlockRow) -> Self {
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TangleExportRow {
+    pub seq: i64,
+    #[sqlx(flatten)]
+    pub block: TangleBlockRow,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct TanglePendingRow {
+    pub id: uuid::Uuid,
+    pub parents: Vec<String>,
+}
+
+/// How a block is addressed in `GET /tangle/:ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+    Id(uuid::Uuid),
+    Hash([u8; 32]),
+}
+
+impl std::str::FromStr for BlockRef {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = uuid::Uuid::parse_str(s) {
+            return Ok(BlockRef::Id(id));
+        }
+        let mut hash = [0u8; 32];
+        hex::decode_to_slice(s, &mut hash).map_err(|_| ())?;
+        Ok(BlockRef::Hash(hash))
+    }
+}
+
+impl TangleBlockDto {
+    pub fn from_row(r: TangleBlockRow, thresholds: &ConfirmationThresholds) -> Self {
         TangleBlockDto {
             id: r.id,
             parents: r.parents,
             data: r.data,
             signature: BASE64_ENGINE.encode(&r.signature),
             public_key: r.public_key,
+            sig_scheme: r.sig_scheme,
+            timestamp_ms: r.timestamp_ms,
+            supersedes: r.supersedes,
+            tombstone: r.tombstone,
+            hash: r.hash.map(hex::encode),
+            cumulative_weight: r.cumulative_weight,
+            confirmation: thresholds.classify(r.cumulative_weight),
             created_at: r.created_at,
         }
     }
 }
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TangleImportReport {
+    pub imported: u64,
+    /// Blocks that were already stored.
+    pub existing: u64,
+    /// Blocks quarantined because their parents are still missing.
+    pub pending: u64,
+    pub rejected: Vec<TangleImportRejection>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TangleImportRejection {
+    /// 1-based line of the archive; absent for blocks pulled from a peer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    pub id: Option<uuid::Uuid>,
+    pub error: String,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TangleBatchResponse {
+    pub stored: u64,
+    pub existing: u64,
+    pub pending: u64,
+    pub rejected: u64,
+    /// One entry per submitted block, in request order.
+    pub results: Vec<TangleBatchItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TangleBatchItem {
+    pub index: usize,
+    /// The status a single `POST /tangle/blocks` would have answered with.
+    pub status: u16,
+    pub block: Option<TangleBlockDto>,
+    pub error: Option<String>,
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Body of `POST /tangle/sync/tips`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TipExchange {
+    pub tips: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TipExchangeResponse {
+    pub tips: Vec<uuid::Uuid>,
+    /// Tips from the request this node does not hold.
+    pub unknown: Vec<uuid::Uuid>,
+}
+
+/// Blocks a peer asks for by id during sync.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockFetch {
+    pub ids: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TangleSyncReport {
+    pub peer: String,
+    /// Our tips the peer reported as unknown.
+    pub peer_missing: usize,
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub ingest: TangleImportReport,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SigningKeyCreate {
+    /// Base64 ed25519 public key.
+    pub public_key: String,
+    /// Base64 signature over `crypto::key_registration_message`.
+    pub proof: String,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SigningKeyDto {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub public_key: String,
+    pub label: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct CheckpointRow {
+    pub id: i64,
+    pub from_seq: i64,
+    pub to_seq: i64,
+    pub block_count: i64,
+    pub root: Vec<u8>,
+    pub chain: Vec<u8>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckpointDto {
+    pub id: i64,
+    /// Exclusive lower bound of the covered seq range.
+    pub from_seq: i64,
+    /// Inclusive upper bound of the covered seq range.
+    pub to_seq: i64,
+    pub block_count: i64,
+    /// Hex-encoded Merkle root over the covered blocks in seq order.
+    pub root: String,
+    /// Hex-encoded `crypto::checkpoint_chain_hash` linking to the previous checkpoint.
+    pub chain: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<CheckpointRow> for CheckpointDto {
+    fn from(r: CheckpointRow) -> Self {
+        CheckpointDto {
+            id: r.id,
+            from_seq: r.from_seq,
+            to_seq: r.to_seq,
+            block_count: r.block_count,
+            root: hex::encode(r.root),
+            chain: hex::encode(r.chain),
+            created_at: r.created_at,
+        }
+    }
+}
+
+/// Response of `GET /tangle/:id/proof`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TangleInclusionProof {
+    pub block_id: uuid::Uuid,
+    pub seq: i64,
+    pub checkpoint: CheckpointDto,
+    /// Verify with `crypto::verify_inclusion` against the exported block.
+    pub proof: InclusionProof,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize)]
+pub struct AuditFindingRow {
+    pub id: i64,
+    pub block_id: uuid::Uuid,
+    pub seq: i64,
+    /// One of `audit::FindingKind`.
+    pub kind: String,
+    pub detail: String,
+    pub first_seen_at: chrono::DateTime<chrono::Utc>,
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+    pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Outcome of one audit pass over the whole tangle.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub checked: u64,
+    /// Findings raised by this pass, including ones already on record.
+    pub findings: u64,
+    /// Findings resolved by this pass.
+    pub resolved: u64,
+}
+
+/// A JSON Schema that block `data` with a matching `type` must satisfy.
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize)]
+pub struct DataSchemaRow {
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub schema: serde_json::Value,
+    pub description: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Body of `PUT /tangle/schemas/:type`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataSchemaUpsert {
+    pub schema: serde_json::Value,
+    pub description: Option<String>,
+}
@@ -1,16 +1,63 @@
 use crate::kernel::Plugin;
+use crate::plugins::auth::policy::{Access, AccessRule};
+use crate::plugins::auth::rbac::{OperateTangle, Permission, WriteTangle};
+use crate::plugins::metrics::MetricsPlugin;
+use crate::plugins::tangle::audit::TangleAuditor;
+use crate::plugins::tangle::checkpoint;
+use crate::plugins::tangle::config::TangleConfig;
 use crate::plugins::tangle::handlers::*;
+use crate::plugins::tangle::schema::SchemaRegistry;
+use crate::plugins::tangle::service;
+use crate::plugins::tangle::stream::TangleStream;
+use crate::plugins::tangle::sync::TangleSync;
+use axum::http::Method;
 use axum::{Extension, Router, routing::delete, routing::get, routing::post, routing::put};
+use parking_lot::Mutex;
 use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 
 pub struct TanglePlugin {
     pub pool: PgPool,
+    pub config: Arc<TangleConfig>,
+    sync: Arc<TangleSync>,
+    stream: Arc<TangleStream>,
+    auditor: Arc<TangleAuditor>,
+    schemas: Arc<SchemaRegistry>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    audit_task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl TanglePlugin {
     #[allow(dead_code)]
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self::with_config(pool, TangleConfig::from_env())
+    }
+
+    pub fn with_config(pool: PgPool, config: TangleConfig) -> Self {
+        let config = Arc::new(config);
+        let schemas = Arc::new(SchemaRegistry::new(pool.clone(), config.clone()));
+        Self {
+            sync: Arc::new(TangleSync::new(
+                pool.clone(),
+                config.clone(),
+                schemas.clone(),
+            )),
+            stream: Arc::new(TangleStream::new(pool.clone())),
+            auditor: Arc::new(TangleAuditor::new(pool.clone(), config.clone())),
+            schemas,
+            tasks: Mutex::new(Vec::new()),
+            audit_task: Mutex::new(None),
+            pool,
+            config,
+        }
+    }
+
+    /// Publishes the auditor's counters through `metrics`.
+    pub fn with_metrics(self, metrics: &MetricsPlugin) -> Self {
+        self.auditor.metrics.register(metrics);
+        self
     }
 }
 
@@ -21,13 +68,88 @@ impl Plugin for TanglePlugin {
             .route("/", get(list_blocks))
             .route("/blocks", post(create_block))
             .route("/blocks", get(list_blocks))
+            .route("/blocks/batch", post(create_blocks_batch))
+            .route("/tips", get(list_tips))
+            .route("/tips/select", get(select_tips))
+            .route("/stream", get(stream_blocks))
+            .route("/checkpoints", get(list_checkpoints))
+            .route("/checkpoints", post(create_checkpoint))
+            .route("/audit", post(run_audit))
+            .route("/audit/findings", get(list_audit_findings))
+            .route("/export", get(export_blocks))
+            .route("/import", post(import_blocks))
+            .route("/sync", post(sync_now))
+            .route("/sync/tips", post(exchange_tips))
+            .route("/sync/blocks", post(fetch_blocks))
+            .route("/schemas", get(list_schemas))
+            .route("/schemas/:type", put(put_schema))
+            .route("/schemas/:type", delete(delete_schema))
+            .route("/keys", post(register_key))
+            .route("/keys", get(list_keys))
+            .route("/keys/:id", delete(revoke_key))
             .route("/:id", get(get_block))
-            .route("/:id", put(update_block))
-            .route("/:id", delete(delete_block))
+            .route("/:id/effective", get(get_effective_block))
+            .route("/:id/history", get(get_block_history))
+            .route("/:id/children", get(get_children))
+            .route("/:id/ancestors", get(get_ancestors))
+            .route("/:id/descendants", get(get_descendants))
+            .route("/:id/proof", get(get_block_proof))
             .layer(Extension(self.pool.clone()))
+            .layer(Extension(self.config.clone()))
+            .layer(Extension(self.sync.clone()))
+            .layer(Extension(self.stream.clone()))
+            .layer(Extension(self.auditor.clone()))
+            .layer(Extension(self.schemas.clone()))
     }
 
     fn name(&self) -> &'static str {
         "tangle"
     }
+
+    fn access_rules(&self) -> Vec<AccessRule> {
+        let operate = Access::Permission(OperateTangle::NAME);
+        let write = Access::Permission(WriteTangle::NAME);
+        vec![
+            AccessRule::on(Method::POST, "/blocks", write),
+            AccessRule::on(Method::POST, "/blocks/batch", write),
+            AccessRule::any("/keys", Access::Bearer),
+            AccessRule::any("/keys/:id", Access::Bearer),
+            AccessRule::any("/schemas", Access::Admin),
+            AccessRule::any("/schemas/:type", Access::Admin),
+            AccessRule::any("/audit", Access::Admin),
+            AccessRule::any("/audit/findings", Access::Admin),
+            AccessRule::on(Method::POST, "/checkpoints", operate),
+            AccessRule::on(Method::POST, "/import", operate),
+            AccessRule::on(Method::POST, "/sync", operate),
+        ]
+    }
+
+    async fn on_start(&self) {
+        let mut tasks = self.tasks.lock();
+        tasks.push(self.stream.spawn());
+        tasks.extend(self.sync.spawn());
+        tasks.extend(checkpoint::spawn(self.pool.clone(), self.config.clone()));
+        tasks.extend(service::spawn_pending_sweep(
+            self.pool.clone(),
+            self.config.clone(),
+        ));
+        *self.audit_task.lock() = self.auditor.spawn();
+    }
+
+    async fn on_shutdown(&self) {
+        for task in self.tasks.lock().drain(..) {
+            task.abort();
+        }
+        // let the auditor finish its batch rather than drop it half-recorded
+        self.auditor.stop();
+        let audit_task = self.audit_task.lock().take();
+        if let Some(mut task) = audit_task
+            && tokio::time::timeout(Duration::from_secs(10), &mut task)
+                .await
+                .is_err()
+        {
+            tracing::warn!("tangle auditor did not stop in time");
+            task.abort();
+        }
+    }
 }
@@ -1,36 +1,195 @@
 use crate::http_error::AppError;
-use crate::plugins::tangle::models::TangleBlockRow;
-use serde_json::Value;
-use sqlx::PgPool;
+use crate::plugins::communication::shared::PageRequest;
+use crate::plugins::tangle::models::{
+    AuditFindingRow, BlockFilter, CheckpointRow, DataSchemaRow, NewBlock, SigningKeyDto,
+    TangleBlockRow, TangleExportRow, TanglePendingRow,
+};
+use sqlx::postgres::PgArguments;
+use sqlx::{PgConnection, PgExecutor, PgPool, Postgres};
 use uuid::Uuid;
 
-pub async fn insert_block(
-    pool: &PgPool,
-    id: Option<Uuid>,
-    parents: &Vec<String>,
-    data: &Value,
-    signature: &[u8],
-    public_key: &str,
-) -> Result<TangleBlockRow, AppError> {
+const BLOCK_COLUMNS: &str = "id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash, cumulative_weight, created_at";
+
+/// Returns `None` when a block with the same hash is already stored,
+/// including one a concurrent transaction committed while this one waited.
+pub async fn insert_block<'e, E>(
+    executor: E,
+    block: &NewBlock,
+) -> Result<Option<TangleBlockRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let row = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "INSERT INTO public.tangle_blocks (id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (hash) WHERE hash IS NOT NULL DO NOTHING RETURNING {}",
+        BLOCK_COLUMNS
+    ))
+    .bind(block.id)
+    .bind(block.parent_strings())
+    .bind(&block.data)
+    .bind(&block.signature)
+    .bind(&block.public_key)
+    .bind(block.sig_scheme.as_i16())
+    .bind(block.timestamp_ms)
+    .bind(block.supersedes)
+    .bind(block.tombstone)
+    .bind(&block.hash[..])
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(row)
+}
+
+pub async fn find_missing_parents<'e, E>(
+    executor: E,
+    parents: &[Uuid],
+) -> Result<Vec<Uuid>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    if parents.is_empty() {
+        return Ok(Vec::new());
+    }
+    let existing: Vec<Uuid> =
+        sqlx::query_scalar::<_, Uuid>("SELECT id FROM public.tangle_blocks WHERE id = ANY($1)")
+            .bind(parents)
+            .fetch_all(executor)
+            .await
+            .map_err(AppError::from)?;
+
+    Ok(parents
+        .iter()
+        .filter(|p| !existing.contains(p))
+        .copied()
+        .collect())
+}
+
+pub async fn insert_pending_block<'e, E>(
+    executor: E,
+    block: &NewBlock,
+    missing: &[Uuid],
+) -> Result<TangleBlockRow, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let missing: Vec<String> = missing.iter().map(|m| m.to_string()).collect();
     let row = sqlx::query_as::<_, TangleBlockRow>(
-        "INSERT INTO public.tangle_blocks (id, parents, data, signature, public_key) VALUES (COALESCE($1, gen_random_uuid()), $2, $3, $4, $5) RETURNING id, parents, data, signature, public_key, created_at"
+        "INSERT INTO public.tangle_pending_blocks (id, parents, missing_parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash, 0::bigint AS cumulative_weight, received_at AS created_at"
     )
-    .bind(id)
-    .bind(parents)
-    .bind(data)
-    .bind(signature)
-    .bind(public_key)
-    .fetch_one(pool)
+    .bind(block.id)
+    .bind(block.parent_strings())
+    .bind(missing)
+    .bind(&block.data)
+    .bind(&block.signature)
+    .bind(&block.public_key)
+    .bind(block.sig_scheme.as_i16())
+    .bind(block.timestamp_ms)
+    .bind(block.supersedes)
+    .bind(block.tombstone)
+    .bind(&block.hash[..])
+    .fetch_one(executor)
     .await
     .map_err(AppError::from)?;
 
     Ok(row)
 }
 
-pub async fn get_block(pool: &PgPool, id: Uuid) -> Result<TangleBlockRow, AppError> {
-    let row = sqlx::query_as::<_, TangleBlockRow>(
-        "SELECT id, parents, data, signature, public_key, created_at FROM public.tangle_blocks WHERE id = $1"
+/// Advisory lock space for [`lock_block_ids`], apart from the single-key
+/// locks such as the checkpoint lock.
+const BLOCK_ID_LOCK_CLASS: i32 = 0x746e_676c; // "tngl"
+
+/// Takes a transaction-scoped advisory lock per block id, in id order so
+/// that concurrent callers cannot deadlock on the same set. Storing a block
+/// locks its id; quarantining a block locks its missing parents, so that a
+/// parent is never stored while a pending child is being inserted unseen.
+pub async fn lock_block_ids(conn: &mut PgConnection, ids: &[Uuid]) -> Result<(), AppError> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    for id in ids {
+        sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+            .bind(BLOCK_ID_LOCK_CLASS)
+            .bind(id.to_string())
+            .execute(&mut *conn)
+            .await
+            .map_err(AppError::from)?;
+    }
+    Ok(())
+}
+
+pub async fn pending_waiting_on(
+    conn: &mut PgConnection,
+    parent: Uuid,
+) -> Result<Vec<TanglePendingRow>, AppError> {
+    let rows = sqlx::query_as::<_, TanglePendingRow>(
+        "SELECT id, parents FROM public.tangle_pending_blocks WHERE missing_parents @> ARRAY[$1::text] FOR UPDATE"
     )
+    .bind(parent.to_string())
+    .fetch_all(conn)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Quarantined blocks of which at least one missing parent has since been
+/// stored, oldest first. Rows locked by another transaction are skipped.
+pub async fn pending_with_stored_parents(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<TanglePendingRow>, AppError> {
+    let rows = sqlx::query_as::<_, TanglePendingRow>(
+        "SELECT p.id, p.parents FROM public.tangle_pending_blocks p WHERE EXISTS (SELECT 1 FROM public.tangle_blocks t WHERE t.id = ANY(p.missing_parents::uuid[])) ORDER BY p.received_at LIMIT $1 FOR UPDATE SKIP LOCKED"
+    )
+    .bind(limit)
+    .fetch_all(conn)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+pub async fn set_pending_missing(
+    conn: &mut PgConnection,
+    id: Uuid,
+    missing: &[Uuid],
+) -> Result<(), AppError> {
+    let missing: Vec<String> = missing.iter().map(|m| m.to_string()).collect();
+    sqlx::query("UPDATE public.tangle_pending_blocks SET missing_parents = $1 WHERE id = $2")
+        .bind(missing)
+        .bind(id)
+        .execute(conn)
+        .await
+        .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Moves a quarantined block into `tangle_blocks`. Returns false if a block
+/// with the same id had already been stored by another path.
+pub async fn promote_pending(conn: &mut PgConnection, id: Uuid) -> Result<bool, AppError> {
+    let inserted = sqlx::query(
+        "INSERT INTO public.tangle_blocks (id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash) SELECT id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash FROM public.tangle_pending_blocks WHERE id = $1 ON CONFLICT (id) DO NOTHING"
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .map_err(AppError::from)?
+    .rows_affected();
+
+    sqlx::query("DELETE FROM public.tangle_pending_blocks WHERE id = $1")
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(inserted > 0)
+}
+
+pub async fn get_block(pool: &PgPool, id: Uuid) -> Result<TangleBlockRow, AppError> {
+    let row = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks WHERE id = $1",
+        BLOCK_COLUMNS
+    ))
     .bind(id)
     .fetch_one(pool)
     .await
@@ -39,44 +198,300 @@ pub async fn get_block(pool: &PgPool, id: Uuid) -> Result<TangleBlockRow, AppErr
     Ok(row)
 }
 
+/// `WHERE` clause of `list_blocks`, binding a `BlockFilter` as `$1..$8`.
+const BLOCK_FILTER: &str = "($1::bigint IS NULL OR cumulative_weight >= $1) AND ($2::bigint IS NULL OR cumulative_weight < $2) AND ($3::jsonb IS NULL OR data @> $3) AND ($4::text[] IS NULL OR data #> $4 = $5::jsonb) AND ($6::text IS NULL OR public_key = $6) AND ($7::timestamptz IS NULL OR created_at >= $7) AND ($8::timestamptz IS NULL OR created_at < $8)";
+
+fn bind_filter<'q, O>(
+    query: sqlx::query::QueryAs<'q, Postgres, O, PgArguments>,
+    filter: &'q BlockFilter,
+) -> sqlx::query::QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(filter.min_weight)
+        .bind(filter.max_weight)
+        .bind(&filter.contains)
+        .bind(&filter.path)
+        .bind(&filter.path_value)
+        .bind(&filter.public_key)
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+}
+
+/// One page of blocks, newest first, plus the filtered total when `page.count`.
 pub async fn list_blocks(
     pool: &PgPool,
-    per_page: i64,
-    offset: i64,
-) -> Result<(Vec<TangleBlockRow>, i64), AppError> {
-    let rows: Vec<TangleBlockRow> = sqlx::query_as::<_, TangleBlockRow>(
-        "SELECT id, parents, data, signature, public_key, created_at FROM public.tangle_blocks ORDER BY created_at DESC LIMIT $1 OFFSET $2"
-    )
-    .bind(per_page)
-    .bind(offset)
-    .fetch_all(pool)
-    .await
-    .map_err(AppError::from)?;
+    page: &PageRequest,
+    filter: &BlockFilter,
+) -> Result<(Vec<TangleBlockRow>, Option<i64>), AppError> {
+    let sql = format!(
+        "SELECT {} FROM public.tangle_blocks WHERE {} AND ($9::timestamptz IS NULL OR (created_at, id) < ($9, $10::uuid)) ORDER BY created_at DESC, id DESC LIMIT $11 OFFSET $12",
+        BLOCK_COLUMNS, BLOCK_FILTER
+    );
+    let rows: Vec<TangleBlockRow> = bind_filter(sqlx::query_as(&sql), filter)
+        .bind(page.cursor.map(|c| c.created_at))
+        .bind(page.cursor.map(|c| c.id))
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(pool)
+        .await
+        .map_err(AppError::from)?;
 
-    let total: i64 = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM public.tangle_blocks")
+    if !page.count {
+        return Ok((rows, None));
+    }
+    let count_sql = format!(
+        "SELECT COUNT(*) FROM public.tangle_blocks WHERE {}",
+        BLOCK_FILTER
+    );
+    let (total,): (i64,) = bind_filter(sqlx::query_as(&count_sql), filter)
         .fetch_one(pool)
         .await
         .map_err(AppError::from)?;
 
-    Ok((rows, total))
+    Ok((rows, Some(total)))
+}
+
+/// Adds one approval to every distinct ancestor reachable from `parents`,
+/// called once per newly stored block.
+///
+/// The walk stops at blocks whose weight already reached `confirmed_at`:
+/// every ancestor of such a block has at least its weight, so nothing past
+/// it can change state. Weights of confirmed blocks are therefore lower
+/// bounds, and the walk stays within the unconfirmed tail of the tangle
+/// instead of reaching genesis on every insert.
+pub async fn add_approval(
+    conn: &mut PgConnection,
+    parents: &[Uuid],
+    confirmed_at: i64,
+) -> Result<(), AppError> {
+    if parents.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "WITH RECURSIVE anc(id) AS (SELECT id FROM public.tangle_blocks WHERE id = ANY($1::uuid[]) AND cumulative_weight < $2 UNION SELECT p.id FROM anc a JOIN public.tangle_blocks c ON c.id = a.id JOIN public.tangle_blocks p ON p.id = ANY(c.parents::uuid[]) WHERE p.cumulative_weight < $2) UPDATE public.tangle_blocks SET cumulative_weight = cumulative_weight + 1 WHERE id IN (SELECT id FROM anc)",
+    )
+    .bind(parents)
+    .bind(confirmed_at)
+    .execute(conn)
+    .await
+    .map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn find_block<'e, E>(executor: E, id: Uuid) -> Result<Option<TangleBlockRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let row = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks WHERE id = $1",
+        BLOCK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(row)
 }
 
-pub async fn update_block(
+pub async fn find_block_by_hash<'e, E>(
+    executor: E,
+    hash: &[u8; 32],
+) -> Result<Option<TangleBlockRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let row = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks WHERE hash = $1",
+        BLOCK_COLUMNS
+    ))
+    .bind(&hash[..])
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(row)
+}
+
+pub async fn find_pending_by_hash<'e, E>(
+    executor: E,
+    hash: &[u8; 32],
+) -> Result<Option<TangleBlockRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let row = sqlx::query_as::<_, TangleBlockRow>(
+        "SELECT id, parents, data, signature, public_key, sig_scheme, timestamp_ms, supersedes, tombstone, hash, 0::bigint AS cumulative_weight, received_at AS created_at FROM public.tangle_pending_blocks WHERE hash = $1 LIMIT 1"
+    )
+    .bind(&hash[..])
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(row)
+}
+
+pub async fn superseded_by<'e, E>(executor: E, id: Uuid) -> Result<Option<Uuid>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    let next =
+        sqlx::query_scalar::<_, Uuid>("SELECT id FROM public.tangle_blocks WHERE supersedes = $1")
+            .bind(id)
+            .fetch_optional(executor)
+            .await
+            .map_err(AppError::from)?;
+
+    Ok(next)
+}
+
+/// The full supersession chain `id` belongs to, from the original block to
+/// the current head.
+pub async fn supersession_chain(pool: &PgPool, id: Uuid) -> Result<Vec<TangleBlockRow>, AppError> {
+    let root: Uuid = sqlx::query_scalar::<_, Uuid>(
+        "WITH RECURSIVE back(id, supersedes) AS (SELECT id, supersedes FROM public.tangle_blocks WHERE id = $1 UNION ALL SELECT t.id, t.supersedes FROM public.tangle_blocks t JOIN back b ON t.id = b.supersedes) SELECT id FROM back WHERE supersedes IS NULL",
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "WITH RECURSIVE chain(id, depth) AS (SELECT id, 0 FROM public.tangle_blocks WHERE id = $1 UNION ALL SELECT t.id, c.depth + 1 FROM public.tangle_blocks t JOIN chain c ON t.supersedes = c.id) SELECT {} FROM public.tangle_blocks JOIN chain USING (id) ORDER BY chain.depth",
+        BLOCK_COLUMNS
+    ))
+    .bind(root)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Blocks that list `id` among their parents.
+pub async fn children(pool: &PgPool, id: Uuid) -> Result<Vec<TangleBlockRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks WHERE parents @> ARRAY[$1::text] ORDER BY created_at, id",
+        BLOCK_COLUMNS
+    ))
+    .bind(id.to_string())
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Ancestors of `id` up to `max_depth` parent hops away, nearest first.
+/// Blocks reachable over several paths are reported at their shortest distance.
+pub async fn ancestors(
     pool: &PgPool,
     id: Uuid,
-    parents: Option<Vec<String>>,
-    data: Option<Value>,
-    signature: Option<Vec<u8>>,
-    public_key: Option<String>,
-) -> Result<TangleBlockRow, AppError> {
-    let row = sqlx::query_as::<_, TangleBlockRow>(
-        "UPDATE public.tangle_blocks SET parents = COALESCE($1, parents), data = COALESCE($2, data), signature = COALESCE($3, signature), public_key = COALESCE($4, public_key) WHERE id = $5 RETURNING id, parents, data, signature, public_key, created_at"
+    max_depth: i32,
+) -> Result<Vec<TangleBlockRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "WITH RECURSIVE anc(id, depth) AS (SELECT unnest(parents)::uuid, 1 FROM public.tangle_blocks WHERE id = $1 UNION SELECT unnest(t.parents)::uuid, a.depth + 1 FROM public.tangle_blocks t JOIN anc a ON t.id = a.id WHERE a.depth < $2) SELECT {} FROM public.tangle_blocks JOIN (SELECT id, MIN(depth) AS depth FROM anc GROUP BY id) reached USING (id) ORDER BY reached.depth, created_at, id",
+        BLOCK_COLUMNS
+    ))
+    .bind(id)
+    .bind(max_depth)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Descendants of `id` up to `max_depth` child hops away, nearest first.
+pub async fn descendants(
+    pool: &PgPool,
+    id: Uuid,
+    max_depth: i32,
+) -> Result<Vec<TangleBlockRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "WITH RECURSIVE des(id, depth) AS (SELECT id, 1 FROM public.tangle_blocks WHERE parents @> ARRAY[$1::text] UNION SELECT t.id, d.depth + 1 FROM public.tangle_blocks t JOIN des d ON t.parents @> ARRAY[d.id::text] WHERE d.depth < $2) SELECT {} FROM public.tangle_blocks JOIN (SELECT id, MIN(depth) AS depth FROM des GROUP BY id) reached USING (id) ORDER BY reached.depth, created_at, id",
+        BLOCK_COLUMNS
+    ))
+    .bind(id.to_string())
+    .bind(max_depth)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Blocks no other block references as a parent, newest first.
+pub async fn tips(pool: &PgPool, limit: i64) -> Result<Vec<TangleBlockRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks b WHERE NOT EXISTS (SELECT 1 FROM public.tangle_blocks c WHERE c.parents @> ARRAY[b.id::text]) ORDER BY created_at DESC, id LIMIT $1",
+        BLOCK_COLUMNS
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+pub async fn find_blocks(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<TangleBlockRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleBlockRow>(&format!(
+        "SELECT {} FROM public.tangle_blocks WHERE id = ANY($1)",
+        BLOCK_COLUMNS
+    ))
+    .bind(ids)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Children of `id` with their walk weight: one plus their cumulative weight.
+pub async fn child_weights(pool: &PgPool, id: Uuid) -> Result<Vec<(Uuid, i64)>, AppError> {
+    let rows = sqlx::query_as::<_, (Uuid, i64)>(
+        "SELECT id, cumulative_weight + 1 FROM public.tangle_blocks WHERE parents @> ARRAY[$1::text] ORDER BY id",
     )
-    .bind(parents)
-    .bind(data)
-    .bind(signature)
+    .bind(id.to_string())
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Stored blocks after `after_seq` in insertion order. A block can only be
+/// stored once its parents are, so this order is topological.
+pub async fn export_batch(
+    pool: &PgPool,
+    after_seq: i64,
+    limit: i64,
+) -> Result<Vec<TangleExportRow>, AppError> {
+    let rows = sqlx::query_as::<_, TangleExportRow>(&format!(
+        "SELECT seq, {} FROM public.tangle_blocks WHERE seq > $1 ORDER BY seq LIMIT $2",
+        BLOCK_COLUMNS
+    ))
+    .bind(after_seq)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+pub async fn insert_signing_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    public_key: &str,
+    label: Option<&str>,
+) -> Result<SigningKeyDto, AppError> {
+    let row = sqlx::query_as::<_, SigningKeyDto>(
+        "INSERT INTO public.tangle_signing_keys (user_id, public_key, label) VALUES ($1, $2, $3) RETURNING id, user_id, public_key, label, created_at, revoked_at",
+    )
+    .bind(user_id)
     .bind(public_key)
-    .bind(id)
+    .bind(label)
     .fetch_one(pool)
     .await
     .map_err(AppError::from)?;
@@ -84,11 +499,292 @@ pub async fn update_block(
     Ok(row)
 }
 
-pub async fn delete_block(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
-    sqlx::query("DELETE FROM public.tangle_blocks WHERE id = $1")
+pub async fn list_signing_keys(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<SigningKeyDto>, AppError> {
+    let rows = sqlx::query_as::<_, SigningKeyDto>(
+        "SELECT id, user_id, public_key, label, created_at, revoked_at FROM public.tangle_signing_keys WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(rows)
+}
+
+/// Revokes one of `user_id`'s keys. Returns false if no such active key exists.
+pub async fn revoke_signing_key(pool: &PgPool, user_id: Uuid, id: Uuid) -> Result<bool, AppError> {
+    let res = sqlx::query(
+        "UPDATE public.tangle_signing_keys SET revoked_at = now() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// The user an active registered key belongs to.
+pub async fn active_key_owner(pool: &PgPool, public_key: &str) -> Result<Option<Uuid>, AppError> {
+    let owner = sqlx::query_scalar::<_, Uuid>(
+        "SELECT user_id FROM public.tangle_signing_keys WHERE public_key = $1 AND revoked_at IS NULL",
+    )
+    .bind(public_key)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(owner)
+}
+
+const CHECKPOINT_COLUMNS: &str = "id, from_seq, to_seq, block_count, root, chain, created_at";
+
+pub async fn latest_checkpoint<'e, E>(executor: E) -> Result<Option<CheckpointRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, CheckpointRow>(&format!(
+        "SELECT {} FROM public.tangle_checkpoints ORDER BY to_seq DESC LIMIT 1",
+        CHECKPOINT_COLUMNS
+    ))
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn list_checkpoints(pool: &PgPool, limit: i64) -> Result<Vec<CheckpointRow>, AppError> {
+    sqlx::query_as::<_, CheckpointRow>(&format!(
+        "SELECT {} FROM public.tangle_checkpoints ORDER BY to_seq DESC LIMIT $1",
+        CHECKPOINT_COLUMNS
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// The checkpoint whose seq range holds `seq`.
+pub async fn checkpoint_covering(
+    pool: &PgPool,
+    seq: i64,
+) -> Result<Option<CheckpointRow>, AppError> {
+    sqlx::query_as::<_, CheckpointRow>(&format!(
+        "SELECT {} FROM public.tangle_checkpoints WHERE from_seq < $1 AND to_seq >= $1",
+        CHECKPOINT_COLUMNS
+    ))
+    .bind(seq)
+    .fetch_optional(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn insert_checkpoint(
+    conn: &mut PgConnection,
+    from_seq: i64,
+    to_seq: i64,
+    block_count: i64,
+    root: &[u8; 32],
+    chain: &[u8; 32],
+) -> Result<CheckpointRow, AppError> {
+    sqlx::query_as::<_, CheckpointRow>(&format!(
+        "INSERT INTO public.tangle_checkpoints (from_seq, to_seq, block_count, root, chain) VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        CHECKPOINT_COLUMNS
+    ))
+    .bind(from_seq)
+    .bind(to_seq)
+    .bind(block_count)
+    .bind(&root[..])
+    .bind(&chain[..])
+    .fetch_one(conn)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Held shared by every transaction that stores blocks, from before their
+/// seq is drawn until commit, and taken exclusively by [`committed_seq`].
+const SEQ_WATERMARK_LOCK: i64 = 0x7461_6e67_6c65_7371; // "tanglesq"
+
+/// Call before inserting into `tangle_blocks`, see [`committed_seq`].
+pub async fn share_seq_watermark(conn: &mut PgConnection) -> Result<(), AppError> {
+    sqlx::query("SELECT pg_advisory_xact_lock_shared($1)")
+        .bind(SEQ_WATERMARK_LOCK)
+        .execute(conn)
+        .await
+        .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// Highest seq after `after_seq` below which no block can still commit.
+///
+/// Seqs are drawn at insert, not at commit, so a transaction still in
+/// flight may hold a lower seq than one already visible. Waiting for the
+/// exclusive watermark lock lets every such transaction finish first; the
+/// ones that start meanwhile draw higher seqs. Runs on a connection of its
+/// own, so callers must not hold another one from `pool` while waiting.
+pub async fn committed_seq(pool: &PgPool, after_seq: i64) -> Result<Option<i64>, AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(SEQ_WATERMARK_LOCK)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+    let seq = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(seq) FROM public.tangle_blocks WHERE seq > $1",
+    )
+    .bind(after_seq)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::from)?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(seq)
+}
+
+/// Blocks with `from_seq < seq <= to_seq` in seq order.
+pub async fn blocks_in_seq_range<'e, E>(
+    executor: E,
+    from_seq: i64,
+    to_seq: i64,
+) -> Result<Vec<TangleExportRow>, AppError>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, TangleExportRow>(&format!(
+        "SELECT seq, {} FROM public.tangle_blocks WHERE seq > $1 AND seq <= $2 ORDER BY seq",
+        BLOCK_COLUMNS
+    ))
+    .bind(from_seq)
+    .bind(to_seq)
+    .fetch_all(executor)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn block_seq(pool: &PgPool, id: Uuid) -> Result<Option<i64>, AppError> {
+    sqlx::query_scalar::<_, i64>("SELECT seq FROM public.tangle_blocks WHERE id = $1")
         .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(AppError::from)
+}
+
+const AUDIT_FINDING_COLUMNS: &str =
+    "id, block_id, seq, kind, detail, first_seen_at, last_seen_at, resolved_at";
+
+/// Records a finding, or refreshes the one already on record. Returns whether
+/// the finding is newly open, i.e. it was unknown or had been resolved.
+pub async fn record_audit_finding(
+    pool: &PgPool,
+    block_id: Uuid,
+    seq: i64,
+    kind: &str,
+    detail: &str,
+) -> Result<bool, AppError> {
+    sqlx::query_scalar::<_, bool>(
+        "WITH prev AS (SELECT resolved_at FROM public.tangle_audit_findings WHERE block_id = $1 AND kind = $3) \
+         INSERT INTO public.tangle_audit_findings (block_id, seq, kind, detail) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (block_id, kind) DO UPDATE \
+         SET seq = EXCLUDED.seq, detail = EXCLUDED.detail, last_seen_at = now(), resolved_at = NULL \
+         RETURNING NOT EXISTS (SELECT 1 FROM prev WHERE resolved_at IS NULL)",
+    )
+    .bind(block_id)
+    .bind(seq)
+    .bind(kind)
+    .bind(detail)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+/// Resolves the open findings of `block_ids` except the `(block_id, kind)`
+/// pairs in `still_open`. Returns how many were resolved.
+pub async fn resolve_audit_findings(
+    pool: &PgPool,
+    block_ids: &[Uuid],
+    still_open: &[(Uuid, &str)],
+) -> Result<u64, AppError> {
+    let (open_ids, open_kinds): (Vec<Uuid>, Vec<&str>) = still_open.iter().copied().unzip();
+    let result = sqlx::query(
+        "UPDATE public.tangle_audit_findings SET resolved_at = now() \
+         WHERE resolved_at IS NULL AND block_id = ANY($1) \
+         AND (block_id, kind) NOT IN (SELECT * FROM UNNEST($2::uuid[], $3::text[]))",
+    )
+    .bind(block_ids)
+    .bind(&open_ids)
+    .bind(&open_kinds)
+    .execute(pool)
+    .await
+    .map_err(AppError::from)?;
+    Ok(result.rows_affected())
+}
+
+pub async fn open_audit_finding_counts(pool: &PgPool) -> Result<Vec<(String, i64)>, AppError> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT kind, count(*) FROM public.tangle_audit_findings WHERE resolved_at IS NULL GROUP BY kind",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn list_audit_findings(
+    pool: &PgPool,
+    kind: Option<&str>,
+    include_resolved: bool,
+    limit: i64,
+) -> Result<Vec<AuditFindingRow>, AppError> {
+    sqlx::query_as::<_, AuditFindingRow>(&format!(
+        "SELECT {} FROM public.tangle_audit_findings \
+         WHERE ($1::text IS NULL OR kind = $1) AND ($2 OR resolved_at IS NULL) \
+         ORDER BY last_seen_at DESC, id DESC LIMIT $3",
+        AUDIT_FINDING_COLUMNS
+    ))
+    .bind(kind)
+    .bind(include_resolved)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+const DATA_SCHEMA_COLUMNS: &str = "data_type, schema, description, created_at, updated_at";
+
+pub async fn list_data_schemas(pool: &PgPool) -> Result<Vec<DataSchemaRow>, AppError> {
+    sqlx::query_as::<_, DataSchemaRow>(&format!(
+        "SELECT {} FROM public.tangle_data_schemas ORDER BY data_type",
+        DATA_SCHEMA_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn upsert_data_schema(
+    pool: &PgPool,
+    data_type: &str,
+    schema: &serde_json::Value,
+    description: Option<&str>,
+) -> Result<DataSchemaRow, AppError> {
+    sqlx::query_as::<_, DataSchemaRow>(&format!(
+        "INSERT INTO public.tangle_data_schemas (data_type, schema, description) VALUES ($1, $2, $3) ON CONFLICT (data_type) DO UPDATE SET schema = EXCLUDED.schema, description = EXCLUDED.description, updated_at = now() RETURNING {}",
+        DATA_SCHEMA_COLUMNS
+    ))
+    .bind(data_type)
+    .bind(schema)
+    .bind(description)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+pub async fn delete_data_schema(pool: &PgPool, data_type: &str) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM public.tangle_data_schemas WHERE data_type = $1")
+        .bind(data_type)
         .execute(pool)
         .await
         .map_err(AppError::from)?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
@@ -0,0 +1,176 @@
+use crate::http_error::AppError;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::models::DataSchemaRow;
+use crate::plugins::tangle::repo;
+use axum::http::StatusCode;
+use jsonschema::JSONSchema;
+use parking_lot::RwLock;
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Validation errors reported per block; the rest are dropped.
+const MAX_ERRORS: usize = 20;
+
+/// Registered data schemas, compiled once and kept in memory.
+///
+/// Writes through this kernel apply at once; writes through other kernels
+/// sharing the database once `schema_refresh_secs` have passed.
+pub struct SchemaRegistry {
+    pool: PgPool,
+    config: Arc<TangleConfig>,
+    compiled: RwLock<Compiled>,
+}
+
+#[derive(Default)]
+struct Compiled {
+    schemas: HashMap<String, Arc<JSONSchema>>,
+    loaded_at: Option<Instant>,
+}
+
+impl SchemaRegistry {
+    pub fn new(pool: PgPool, config: Arc<TangleConfig>) -> Self {
+        Self {
+            pool,
+            config,
+            compiled: RwLock::new(Compiled::default()),
+        }
+    }
+
+    /// Checks `data` against the schema registered for its `type`.
+    pub async fn validate(&self, data: &Value) -> Result<(), AppError> {
+        let data_type = data.get("type").and_then(Value::as_str);
+        let schema = match data_type {
+            Some(t) => self.schema(t).await?,
+            None => None,
+        };
+        let (Some(data_type), Some(schema)) = (data_type, schema) else {
+            if !self.config.require_data_schema {
+                return Ok(());
+            }
+            return Err(match data_type {
+                None => AppError::new(StatusCode::BAD_REQUEST, "missingDataType")
+                    .with_code("missing_data_type"),
+                Some(t) => {
+                    AppError::new(StatusCode::BAD_REQUEST, format!("unknownDataType: {}", t))
+                        .with_code("unknown_data_type")
+                }
+            });
+        };
+
+        let errors: Vec<Value> = match schema.validate(data) {
+            Ok(()) => return Ok(()),
+            Err(errors) => errors
+                .take(MAX_ERRORS)
+                .map(|e| {
+                    json!({
+                        "path": e.instance_path.to_string(),
+                        "schema_path": e.schema_path.to_string(),
+                        "message": e.to_string(),
+                    })
+                })
+                .collect(),
+        };
+        Err(
+            AppError::new(StatusCode::BAD_REQUEST, "schemaValidationFailed")
+                .with_code("schema_validation_failed")
+                .with_details(json!({ "type": data_type, "errors": errors })),
+        )
+    }
+
+    pub async fn list(&self) -> Result<Vec<DataSchemaRow>, AppError> {
+        repo::list_data_schemas(&self.pool).await
+    }
+
+    /// Registers or replaces the schema for `data_type`. Blocks already
+    /// stored are not re-validated.
+    pub async fn register(
+        &self,
+        data_type: &str,
+        schema: Value,
+        description: Option<&str>,
+    ) -> Result<DataSchemaRow, AppError> {
+        check_data_type(data_type)?;
+        let compiled = compile(&schema)?;
+        let row = repo::upsert_data_schema(&self.pool, data_type, &schema, description).await?;
+        self.compiled
+            .write()
+            .schemas
+            .insert(data_type.to_string(), Arc::new(compiled));
+        Ok(row)
+    }
+
+    pub async fn remove(&self, data_type: &str) -> Result<(), AppError> {
+        if !repo::delete_data_schema(&self.pool, data_type).await? {
+            return Err(
+                AppError::new(StatusCode::NOT_FOUND, "schemaNotFound").with_code("not_found")
+            );
+        }
+        self.compiled.write().schemas.remove(data_type);
+        Ok(())
+    }
+
+    async fn schema(&self, data_type: &str) -> Result<Option<Arc<JSONSchema>>, AppError> {
+        let max_age = Duration::from_secs(self.config.schema_refresh_secs);
+        let fresh = self
+            .compiled
+            .read()
+            .loaded_at
+            .is_some_and(|at| at.elapsed() < max_age);
+        if !fresh {
+            self.reload().await?;
+        }
+        Ok(self.compiled.read().schemas.get(data_type).cloned())
+    }
+
+    async fn reload(&self) -> Result<(), AppError> {
+        let rows = repo::list_data_schemas(&self.pool).await?;
+        let mut schemas = HashMap::with_capacity(rows.len());
+        for row in rows {
+            match compile(&row.schema) {
+                Ok(schema) => {
+                    schemas.insert(row.data_type, Arc::new(schema));
+                }
+                // registration compiles first, so this only follows manual edits
+                Err(e) => tracing::warn!("tangle schema {} skipped: {}", row.data_type, e.message),
+            }
+        }
+        *self.compiled.write() = Compiled {
+            schemas,
+            loaded_at: Some(Instant::now()),
+        };
+        Ok(())
+    }
+}
+
+fn compile(schema: &Value) -> Result<JSONSchema, AppError> {
+    JSONSchema::options()
+        .should_validate_formats(true)
+        .compile(schema)
+        .map_err(|e| {
+            AppError::new(StatusCode::BAD_REQUEST, "invalidSchema")
+                .with_code("invalid_schema")
+                .with_details(json!({
+                    "path": e.instance_path.to_string(),
+                    "message": e.to_string(),
+                }))
+        })
+}
+
+/// Type names are path segments of the admin endpoints: 1-64 characters of
+/// `[A-Za-z0-9_.:-]`.
+fn check_data_type(data_type: &str) -> Result<(), AppError> {
+    let valid = !data_type.is_empty()
+        && data_type.len() <= 64
+        && data_type
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::new(StatusCode::BAD_REQUEST, "invalidDataType")
+            .with_code("invalid_data_type"))
+    }
+}
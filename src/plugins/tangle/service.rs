@@ -0,0 +1,679 @@
+use crate::http_error::AppError;
+use crate::plugins::auth::handlers::AuthUser;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::crypto::{
+    SigScheme, SigningPayload, VerifyFailure, check_ed25519_signature, content_address,
+    content_hash, key_registration_message, signing_message,
+};
+use crate::plugins::tangle::models::{
+    NewBlock, TangleBlockCreate, TangleBlockRow, TangleImportRejection, TangleImportReport,
+    TanglePendingRow,
+};
+use crate::plugins::tangle::repo;
+use crate::plugins::tangle::schema::SchemaRegistry;
+use axum::http::StatusCode;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use sqlx::{Connection, PgConnection, PgPool};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+pub enum Submission {
+    Stored(TangleBlockRow),
+    /// The same content was already stored; nothing was written.
+    Existing(TangleBlockRow),
+    Pending(TangleBlockRow),
+}
+
+pub fn parse_parents(id: Option<Uuid>, parents: &[String]) -> Result<Vec<Uuid>, AppError> {
+    let mut parsed: Vec<Uuid> = Vec::with_capacity(parents.len());
+    for raw in parents {
+        let parent = Uuid::parse_str(raw).map_err(|_| {
+            AppError::new(StatusCode::BAD_REQUEST, format!("invalidParentId: {}", raw))
+                .with_code("invalid_parent")
+        })?;
+        if Some(parent) == id {
+            return Err(
+                AppError::new(StatusCode::BAD_REQUEST, "blockCannotReferenceItself")
+                    .with_code("self_parent"),
+            );
+        }
+        if parsed.contains(&parent) {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("duplicateParent: {}", parent),
+            )
+            .with_code("duplicate_parent"));
+        }
+        parsed.push(parent);
+    }
+    Ok(parsed)
+}
+
+pub fn parse_sig_scheme(config: &TangleConfig, raw: Option<i16>) -> Result<SigScheme, AppError> {
+    let scheme = match raw {
+        None => SigScheme::CanonicalV1,
+        Some(v) => SigScheme::try_from(v).map_err(|v| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("unsupportedSigScheme: {}", v),
+            )
+            .with_code("unsupported_sig_scheme")
+        })?,
+    };
+    if scheme == SigScheme::Legacy && !config.allow_legacy_signatures {
+        return Err(
+            AppError::new(StatusCode::BAD_REQUEST, "legacySigSchemeDisabled")
+                .with_code("unsupported_sig_scheme"),
+        );
+    }
+    Ok(scheme)
+}
+
+pub fn decode_signature(raw: &str) -> Result<Vec<u8>, AppError> {
+    BASE64_ENGINE.decode(raw).map_err(|e| {
+        AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("invalidSignatureEncoding: {}", e),
+        )
+        .with_code("invalid_signature")
+    })
+}
+
+/// Checks the block signature against the message its `sig_scheme` defines.
+/// Outside strict mode, blocks whose public key is not a base64 32-byte key
+/// are let through unverified.
+pub fn verify_signature(config: &TangleConfig, block: &NewBlock) -> Result<(), AppError> {
+    let public_key = match BASE64_ENGINE.decode(&block.public_key) {
+        Ok(bytes) if bytes.len() == 32 => bytes,
+        _ if !config.strict_signatures => return Ok(()),
+        _ => return Err(verify_failure_error(VerifyFailure::MalformedPublicKey)),
+    };
+    let timestamp_ms = match (block.sig_scheme, block.timestamp_ms) {
+        (SigScheme::Legacy, ts) => ts.unwrap_or_default(),
+        (SigScheme::CanonicalV1, Some(ts)) => ts,
+        (SigScheme::CanonicalV1, None) => {
+            return Err(AppError::new(StatusCode::BAD_REQUEST, "timestampRequired")
+                .with_code("missing_timestamp"));
+        }
+    };
+    let msg = signing_message(
+        block.sig_scheme,
+        &SigningPayload {
+            id: block.id,
+            parents: &block.parents,
+            data: &block.data,
+            timestamp_ms,
+            supersedes: block.supersedes,
+            tombstone: block.tombstone,
+        },
+    );
+    check_ed25519_signature(&public_key, &msg, &block.signature).map_err(verify_failure_error)
+}
+
+fn verify_failure_error(failure: VerifyFailure) -> AppError {
+    match failure {
+        VerifyFailure::MalformedPublicKey => {
+            AppError::new(StatusCode::BAD_REQUEST, "invalidPublicKey")
+                .with_code("invalid_public_key")
+        }
+        VerifyFailure::MalformedSignature => {
+            AppError::new(StatusCode::BAD_REQUEST, "invalidSignature")
+                .with_code("invalid_signature")
+        }
+        VerifyFailure::Mismatch => {
+            AppError::new(StatusCode::BAD_REQUEST, "signatureVerificationFailed")
+                .with_code("signature_mismatch")
+        }
+    }
+}
+
+/// Checks the supersession markers that can be validated without the database.
+pub fn check_supersession_markers(block: &NewBlock) -> Result<(), AppError> {
+    match block.supersedes {
+        None if block.tombstone => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "tombstoneRequiresSupersedes",
+        )
+        .with_code("invalid_supersession")),
+        None => Ok(()),
+        Some(_) if block.sig_scheme == SigScheme::Legacy => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "supersessionRequiresCanonicalSignature",
+        )
+        .with_code("unsupported_sig_scheme")),
+        Some(target) if !block.parents.contains(&target) => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "supersededBlockMustBeParent",
+        )
+        .with_code("invalid_supersession")),
+        Some(_) => Ok(()),
+    }
+}
+
+/// A block may only replace the current head of a chain, signed by the same key.
+async fn check_supersession(conn: &mut PgConnection, block: &NewBlock) -> Result<(), AppError> {
+    let Some(target_id) = block.supersedes else {
+        return Ok(());
+    };
+    let target = repo::find_block(&mut *conn, target_id)
+        .await?
+        .ok_or_else(|| unknown_parents_error(&[target_id]))?;
+    if target.public_key != block.public_key {
+        return Err(
+            AppError::new(StatusCode::FORBIDDEN, "supersedingKeyMismatch")
+                .with_code("superseder_key_mismatch"),
+        );
+    }
+    if target.tombstone {
+        return Err(
+            AppError::new(StatusCode::GONE, "blockIsTombstoned").with_code("block_tombstoned")
+        );
+    }
+    if let Some(next) = repo::superseded_by(&mut *conn, target_id).await? {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("alreadySupersededBy: {}", next),
+        )
+        .with_code("already_superseded"));
+    }
+    Ok(())
+}
+
+fn unknown_parents_error(missing: &[Uuid]) -> AppError {
+    let ids: Vec<String> = missing.iter().map(|m| m.to_string()).collect();
+    AppError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        format!("unknownParents: {}", ids.join(",")),
+    )
+    .with_code("unknown_parent")
+}
+
+/// Checks that `public_key` is an ed25519 key and that `proof` is its
+/// signature over the registration message for `user_id`.
+pub fn verify_key_possession(user_id: Uuid, public_key: &str, proof: &str) -> Result<(), AppError> {
+    let pk_bytes = BASE64_ENGINE.decode(public_key).map_err(|_| {
+        AppError::new(StatusCode::BAD_REQUEST, "invalidPublicKey").with_code("invalid_public_key")
+    })?;
+    let proof = BASE64_ENGINE.decode(proof).map_err(|_| {
+        AppError::new(StatusCode::BAD_REQUEST, "invalidProof").with_code("invalid_proof")
+    })?;
+    let msg = key_registration_message(user_id, public_key);
+    check_ed25519_signature(&pk_bytes, &msg, &proof).map_err(|failure| match failure {
+        VerifyFailure::MalformedPublicKey => {
+            AppError::new(StatusCode::BAD_REQUEST, "invalidPublicKey")
+                .with_code("invalid_public_key")
+        }
+        VerifyFailure::MalformedSignature | VerifyFailure::Mismatch => {
+            AppError::new(StatusCode::BAD_REQUEST, "proofOfPossessionFailed")
+                .with_code("invalid_proof")
+        }
+    })
+}
+
+/// With `require_registered_keys`, the block must be signed with an active
+/// key registered to the submitting user.
+pub async fn check_key_registration(
+    pool: &PgPool,
+    config: &TangleConfig,
+    user: Option<&AuthUser>,
+    block: &NewBlock,
+) -> Result<(), AppError> {
+    if !config.require_registered_keys {
+        return Ok(());
+    }
+    let user = user.ok_or_else(|| {
+        AppError::new(StatusCode::UNAUTHORIZED, "missingAuthorization").with_code("missing_token")
+    })?;
+    match repo::active_key_owner(pool, &block.public_key).await? {
+        Some(owner) if owner == user.user_id => Ok(()),
+        _ => Err(
+            AppError::new(StatusCode::FORBIDDEN, "signingKeyNotRegistered")
+                .with_code("unregistered_signing_key"),
+        ),
+    }
+}
+
+/// Runs every check that does not need the database and turns a submitted
+/// block into a [`NewBlock`].
+pub fn prepare_block(
+    config: &TangleConfig,
+    payload: TangleBlockCreate,
+) -> Result<NewBlock, AppError> {
+    let parents = parse_parents(payload.id, &payload.parents)?;
+    let sig_scheme = parse_sig_scheme(config, payload.sig_scheme)?;
+    let signature = decode_signature(&payload.signature)?;
+
+    let hash = content_hash(
+        &parents,
+        &payload.data,
+        &payload.public_key,
+        payload.timestamp_ms,
+        payload.supersedes,
+        payload.tombstone,
+    );
+    let block = NewBlock {
+        id: resolve_block_id(config, payload.id, &hash)?,
+        parents,
+        data: payload.data,
+        signature,
+        public_key: payload.public_key,
+        sig_scheme,
+        timestamp_ms: payload.timestamp_ms,
+        supersedes: payload.supersedes,
+        tombstone: payload.tombstone,
+        hash,
+    };
+
+    check_supersession_markers(&block)?;
+    verify_signature(config, &block)?;
+    Ok(block)
+}
+
+/// Picks the id of a new block. With content-addressed ids the id is derived
+/// from the hash and a client-supplied id must match it.
+pub fn resolve_block_id(
+    config: &TangleConfig,
+    requested: Option<Uuid>,
+    hash: &[u8; 32],
+) -> Result<Uuid, AppError> {
+    if !config.content_addressed_ids {
+        return Ok(requested.unwrap_or_else(Uuid::new_v4));
+    }
+    let derived = content_address(hash);
+    match requested {
+        Some(id) if id != derived => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("idDoesNotMatchContent: expected {}", derived),
+        )
+        .with_code("content_id_mismatch")),
+        _ => Ok(derived),
+    }
+}
+
+/// The checks a prepared block goes through before it is submitted by a
+/// user: its data schema and, if required, the signing key's owner.
+pub async fn check_submission(
+    pool: &PgPool,
+    config: &TangleConfig,
+    schemas: &SchemaRegistry,
+    user: Option<&AuthUser>,
+    block: &NewBlock,
+) -> Result<(), AppError> {
+    schemas.validate(&block.data).await?;
+    check_key_registration(pool, config, user, block).await
+}
+
+/// The full `POST /tangle/blocks` path for a block received in bulk from an
+/// archive or a peer. Key ownership is not checked, since the submitting
+/// user is not the block's author.
+pub async fn ingest_block(
+    pool: &PgPool,
+    config: &TangleConfig,
+    schemas: &SchemaRegistry,
+    payload: TangleBlockCreate,
+) -> Result<Submission, AppError> {
+    let block = prepare_block(config, payload)?;
+    schemas.validate(&block.data).await?;
+    submit_block(pool, config, block).await
+}
+
+pub fn record_ingest(
+    report: &mut TangleImportReport,
+    line: Option<u64>,
+    id: Option<Uuid>,
+    outcome: Result<Submission, AppError>,
+) {
+    match outcome {
+        Ok(Submission::Stored(_)) => report.imported += 1,
+        Ok(Submission::Existing(_)) => report.existing += 1,
+        Ok(Submission::Pending(_)) => report.pending += 1,
+        Err(e) => report.rejected.push(TangleImportRejection {
+            line,
+            id,
+            error: e.message,
+            code: e.code,
+        }),
+    }
+}
+
+pub async fn submit_block(
+    pool: &PgPool,
+    config: &TangleConfig,
+    block: NewBlock,
+) -> Result<Submission, AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    let submission = submit_in_tx(&mut tx, config, &block).await?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(submission)
+}
+
+/// Stores or quarantines `block` on a connection that is already inside a
+/// transaction; the caller commits.
+async fn submit_in_tx(
+    conn: &mut PgConnection,
+    config: &TangleConfig,
+    block: &NewBlock,
+) -> Result<Submission, AppError> {
+    check_supersession_markers(block)?;
+
+    // resubmitting a block we already hold is a no-op
+    if let Some(existing) = find_duplicate(conn, block).await? {
+        return Ok(existing);
+    }
+
+    check_supersession(conn, block).await?;
+
+    let mut missing = repo::find_missing_parents(&mut *conn, &block.parents).await?;
+    if !missing.is_empty() {
+        if !config.quarantine_orphans {
+            return Err(unknown_parents_error(&missing));
+        }
+        // a transaction storing one of them would not see our pending row:
+        // wait for it to commit and look again
+        repo::lock_block_ids(conn, &missing).await?;
+        missing = repo::find_missing_parents(&mut *conn, &missing).await?;
+        // an identical submission waiting on the same parents may have
+        // quarantined it meanwhile
+        if let Some(existing) = find_duplicate(conn, block).await? {
+            return Ok(existing);
+        }
+    }
+    if !missing.is_empty() {
+        let row = repo::insert_pending_block(&mut *conn, block, &missing).await?;
+        return Ok(Submission::Pending(row));
+    }
+
+    repo::lock_block_ids(conn, &[block.id]).await?;
+    repo::share_seq_watermark(conn).await?;
+    let Some(row) = repo::insert_block(&mut *conn, block).await? else {
+        // an identical submission committed while we were checking
+        return match repo::find_block_by_hash(&mut *conn, &block.hash).await? {
+            Some(existing) => Ok(Submission::Existing(existing)),
+            None => {
+                Err(AppError::new(StatusCode::CONFLICT, "duplicateKey").with_code("duplicate_key"))
+            }
+        };
+    };
+    repo::add_approval(conn, &block.parents, config.confirmation.confirmed).await?;
+    promote_waiting(conn, config, row.id).await?;
+    Ok(Submission::Stored(row))
+}
+
+/// The stored or quarantined block with the same content as `block`.
+async fn find_duplicate(
+    conn: &mut PgConnection,
+    block: &NewBlock,
+) -> Result<Option<Submission>, AppError> {
+    if let Some(existing) = repo::find_block_by_hash(&mut *conn, &block.hash).await? {
+        return Ok(Some(Submission::Existing(existing)));
+    }
+    Ok(repo::find_pending_by_hash(&mut *conn, &block.hash)
+        .await?
+        .map(Submission::Pending))
+}
+
+/// How `POST /tangle/blocks/batch` treats failing items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchMode {
+    /// All blocks are stored or none is.
+    Atomic,
+    /// Each block is stored on its own savepoint; failures are reported per item.
+    Partial,
+}
+
+impl std::str::FromStr for BatchMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atomic" => Ok(BatchMode::Atomic),
+            "partial" => Ok(BatchMode::Partial),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Runs [`prepare_block`] for every item on the blocking pool, spreading the
+/// signature checks over the available cores.
+pub async fn prepare_batch(
+    config: Arc<TangleConfig>,
+    payloads: Vec<TangleBlockCreate>,
+) -> Vec<Result<NewBlock, AppError>> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = payloads.len().div_ceil(workers).max(1);
+    let mut payloads = payloads.into_iter();
+    let mut tasks = Vec::new();
+    loop {
+        let chunk: Vec<TangleBlockCreate> = payloads.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        let config = config.clone();
+        tasks.push(tokio::task::spawn_blocking(move || {
+            chunk
+                .into_iter()
+                .map(|payload| prepare_block(&config, payload))
+                .collect::<Vec<_>>()
+        }));
+    }
+
+    let mut prepared = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok(results) => prepared.extend(results),
+            Err(e) => {
+                return vec![Err(AppError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    e.to_string(),
+                ))];
+            }
+        }
+    }
+    prepared
+}
+
+/// Stores a batch of prepared blocks in one transaction, parents before the
+/// blocks in the batch that reference them. Results are in input order.
+pub async fn submit_batch(
+    pool: &PgPool,
+    config: &TangleConfig,
+    items: Vec<Result<NewBlock, AppError>>,
+    mode: BatchMode,
+) -> Result<Vec<Result<Submission, AppError>>, AppError> {
+    let mut blocks: Vec<Option<NewBlock>> = Vec::with_capacity(items.len());
+    let mut results: Vec<Option<Result<Submission, AppError>>> = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        match item {
+            Ok(block) => {
+                blocks.push(Some(block));
+                results.push(None);
+            }
+            Err(e) if mode == BatchMode::Atomic => return Err(batch_item_error(index, e)),
+            Err(e) => {
+                blocks.push(None);
+                results.push(Some(Err(e)));
+            }
+        }
+    }
+
+    let (order, cyclic) = batch_order(&blocks);
+    for index in cyclic {
+        if mode == BatchMode::Atomic {
+            return Err(batch_item_error(index, batch_cycle_error()));
+        }
+        results[index] = Some(Err(batch_cycle_error()));
+    }
+
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    for index in order {
+        let Some(block) = &blocks[index] else {
+            continue;
+        };
+        let outcome = match mode {
+            BatchMode::Atomic => Ok(submit_in_tx(&mut tx, config, block)
+                .await
+                .map_err(|e| batch_item_error(index, e))?),
+            BatchMode::Partial => {
+                let mut savepoint = Connection::begin(&mut *tx).await.map_err(AppError::from)?;
+                let outcome = submit_in_tx(&mut savepoint, config, block).await;
+                if outcome.is_ok() {
+                    savepoint.commit().await.map_err(AppError::from)?;
+                } else {
+                    savepoint.rollback().await.map_err(AppError::from)?;
+                }
+                outcome
+            }
+        };
+        results[index] = Some(outcome);
+    }
+    tx.commit().await.map_err(AppError::from)?;
+
+    Ok(results
+        .into_iter()
+        .map(|r| r.expect("every batch item is ordered or rejected"))
+        .collect())
+}
+
+/// Kahn's algorithm over the in-batch parent edges. Returns the indices in
+/// storing order and the indices caught in a cycle.
+fn batch_order(blocks: &[Option<NewBlock>]) -> (Vec<usize>, Vec<usize>) {
+    let index_of: HashMap<Uuid, usize> = blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| b.as_ref().map(|b| (b.id, i)))
+        .collect();
+    let mut waiting_on: Vec<usize> = vec![0; blocks.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); blocks.len()];
+    for (i, block) in blocks.iter().enumerate() {
+        let Some(block) = block else {
+            continue;
+        };
+        for parent in &block.parents {
+            if let Some(&p) = index_of.get(parent)
+                && p != i
+            {
+                waiting_on[i] += 1;
+                children[p].push(i);
+            }
+        }
+    }
+
+    let mut ready: VecDeque<usize> = (0..blocks.len())
+        .filter(|&i| blocks[i].is_some() && waiting_on[i] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(blocks.len());
+    while let Some(i) = ready.pop_front() {
+        order.push(i);
+        for &child in &children[i] {
+            waiting_on[child] -= 1;
+            if waiting_on[child] == 0 {
+                ready.push_back(child);
+            }
+        }
+    }
+    let cyclic = (0..blocks.len())
+        .filter(|&i| blocks[i].is_some() && waiting_on[i] > 0)
+        .collect();
+    (order, cyclic)
+}
+
+fn batch_cycle_error() -> AppError {
+    AppError::new(StatusCode::BAD_REQUEST, "batchParentCycle").with_code("batch_cycle")
+}
+
+fn batch_item_error(index: usize, e: AppError) -> AppError {
+    AppError {
+        message: format!("batchItem {}: {}", index, e.message),
+        ..e
+    }
+}
+
+/// Promotes every quarantined block whose last missing parent is `stored`,
+/// cascading to blocks that were waiting on the promoted ones. Returns how
+/// many were promoted.
+async fn promote_waiting(
+    conn: &mut PgConnection,
+    config: &TangleConfig,
+    stored: Uuid,
+) -> Result<u64, AppError> {
+    let mut promoted = 0;
+    let mut queue = vec![stored];
+    while let Some(parent) = queue.pop() {
+        for pending in repo::pending_waiting_on(conn, parent).await? {
+            if promote_if_ready(conn, config, &pending).await? {
+                promoted += 1;
+                queue.push(pending.id);
+            }
+        }
+    }
+    Ok(promoted)
+}
+
+/// Stores a quarantined block if none of its parents is missing any more,
+/// or else narrows down its missing parents.
+async fn promote_if_ready(
+    conn: &mut PgConnection,
+    config: &TangleConfig,
+    pending: &TanglePendingRow,
+) -> Result<bool, AppError> {
+    let parents: Vec<Uuid> = pending
+        .parents
+        .iter()
+        .filter_map(|p| Uuid::parse_str(p).ok())
+        .collect();
+    let missing = repo::find_missing_parents(&mut *conn, &parents).await?;
+    if !missing.is_empty() {
+        repo::set_pending_missing(conn, pending.id, &missing).await?;
+        return Ok(false);
+    }
+    repo::lock_block_ids(conn, &[pending.id]).await?;
+    repo::share_seq_watermark(conn).await?;
+    if !repo::promote_pending(conn, pending.id).await? {
+        return Ok(false);
+    }
+    repo::add_approval(conn, &parents, config.confirmation.confirmed).await?;
+    tracing::info!("promoted pending tangle block {}", pending.id);
+    Ok(true)
+}
+
+/// Quarantined blocks looked at per sweep.
+const SWEEP_BATCH: i64 = 500;
+
+/// Promotes quarantined blocks whose parents were stored without them being
+/// noticed, e.g. by a kernel that crashed between storing and promoting.
+/// Returns how many blocks were promoted, cascades included.
+pub async fn sweep_pending(pool: &PgPool, config: &TangleConfig) -> Result<u64, AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
+    let mut promoted = 0;
+    for pending in repo::pending_with_stored_parents(&mut tx, SWEEP_BATCH).await? {
+        if promote_if_ready(&mut tx, config, &pending).await? {
+            promoted += 1 + promote_waiting(&mut tx, config, pending.id).await?;
+        }
+    }
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(promoted)
+}
+
+/// Starts the periodic [`sweep_pending`] loop if orphans are quarantined and
+/// an interval is configured.
+pub fn spawn_pending_sweep(pool: PgPool, config: Arc<TangleConfig>) -> Option<JoinHandle<()>> {
+    if !config.quarantine_orphans || config.pending_sweep_secs == 0 {
+        return None;
+    }
+    let every = Duration::from_secs(config.pending_sweep_secs);
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            match sweep_pending(&pool, &config).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("pending sweep promoted {} tangle blocks", n),
+                Err(e) => tracing::warn!("tangle pending sweep failed: {}", e.message),
+            }
+        }
+    }))
+}
@@ -0,0 +1,276 @@
+use crate::http_error::AppError;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::models::{
+    BlockFilter, TangleBlockDto, TangleBlockRow, TangleExportRow,
+};
+use crate::plugins::tangle::repo;
+use axum::response::sse::Event;
+use futures_util::Stream;
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Channel the `tangle_blocks_notify` trigger publishes on.
+const CHANNEL: &str = "tangle_blocks";
+/// Live batches buffered per subscriber before it has to catch up from the database.
+const BUFFER: usize = 1024;
+const REPLAY_BATCH: i64 = 500;
+
+/// Fans out blocks stored by any kernel sharing the database to
+/// `GET /tangle/stream` subscribers.
+///
+/// One `LISTEN` connection per instance wakes up on every committed block
+/// and broadcasts the blocks up to the committed-seq watermark (see
+/// [`repo::committed_seq`]) in seq order; subscribers filter on their own.
+/// A block whose seq was drawn before another's but that committed after it
+/// is therefore never overtaken, and every event id marks a point before
+/// which nothing more will be sent.
+pub struct TangleStream {
+    pool: PgPool,
+    sender: broadcast::Sender<Arc<Published>>,
+}
+
+/// Every block with a seq in `(after, through]`, in seq order.
+struct Published {
+    after: i64,
+    through: i64,
+    rows: Vec<Arc<TangleExportRow>>,
+}
+
+impl TangleStream {
+    pub fn new(pool: PgPool) -> Self {
+        let (sender, _) = broadcast::channel(BUFFER);
+        Self { pool, sender }
+    }
+
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let stream = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = stream.listen().await {
+                    tracing::warn!("tangle stream listener failed: {}", e);
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        })
+    }
+
+    async fn listen(&self) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(CHANNEL).await?;
+        // watermark published so far; unknown while nobody is listening
+        let mut published: Option<i64> = None;
+        loop {
+            let notification = listener.recv().await?;
+            let Ok(seq) = notification.payload().parse::<i64>() else {
+                continue;
+            };
+            // nobody is listening; skip the lookup
+            if self.sender.receiver_count() == 0 {
+                published = None;
+                continue;
+            }
+            if published.is_some_and(|p| seq <= p) {
+                continue;
+            }
+            match self.publish(published.unwrap_or(seq - 1)).await {
+                Ok(through) => published = Some(through),
+                Err(e) => {
+                    tracing::warn!("tangle stream could not load block {}: {}", seq, e.message);
+                    published = None;
+                }
+            }
+        }
+    }
+
+    /// Broadcasts the blocks committed after `after`, up to the watermark,
+    /// and returns the watermark.
+    async fn publish(&self, after: i64) -> Result<i64, AppError> {
+        let Some(through) = repo::committed_seq(&self.pool, after).await? else {
+            return Ok(after);
+        };
+        let rows = repo::blocks_in_seq_range(&self.pool, after, through).await?;
+        let _ = self.sender.send(Arc::new(Published {
+            after,
+            through,
+            rows: rows.into_iter().map(Arc::new).collect(),
+        }));
+        Ok(through)
+    }
+
+    /// SSE events for blocks matching `filter`. With `last_event_id`, blocks
+    /// stored after that seq are replayed from the database first; without,
+    /// the stream starts at the current watermark.
+    pub async fn subscribe(
+        &self,
+        config: Arc<TangleConfig>,
+        filter: BlockFilter,
+        last_event_id: Option<i64>,
+    ) -> Result<impl Stream<Item = Result<Event, axum::Error>> + use<>, AppError> {
+        // subscribe before replaying so nothing committed meanwhile is lost
+        let receiver = self.sender.subscribe();
+        let last_seq = match last_event_id {
+            Some(seq) => seq,
+            None => repo::committed_seq(&self.pool, 0).await?.unwrap_or(0),
+        };
+        let state = Subscriber {
+            pool: self.pool.clone(),
+            receiver,
+            config,
+            filter,
+            last_seq,
+            replay_to: None,
+            catching_up: last_event_id.is_some(),
+            backlog: VecDeque::new(),
+        };
+        Ok(futures_util::stream::unfold(
+            state,
+            |mut state| async move {
+                let row = state.next_match().await?;
+                let event = Event::default()
+                    .event("block")
+                    .id(row.seq.to_string())
+                    .json_data(TangleBlockDto::from_row(
+                        row.block.clone(),
+                        &state.config.confirmation,
+                    ));
+                Some((event, state))
+            },
+        ))
+    }
+}
+
+struct Subscriber {
+    pool: PgPool,
+    receiver: broadcast::Receiver<Arc<Published>>,
+    config: Arc<TangleConfig>,
+    filter: BlockFilter,
+    /// Every block up to this seq is delivered, skipped or in the backlog.
+    last_seq: i64,
+    /// Watermark the current replay runs up to.
+    replay_to: Option<i64>,
+    /// Read from the database instead of the channel until caught up.
+    catching_up: bool,
+    backlog: VecDeque<Arc<TangleExportRow>>,
+}
+
+impl Subscriber {
+    async fn next_match(&mut self) -> Option<Arc<TangleExportRow>> {
+        loop {
+            if let Some(row) = self.backlog.pop_front() {
+                if matches(&self.filter, &row.block) {
+                    return Some(row);
+                }
+                continue;
+            }
+            if self.catching_up {
+                if let Err(e) = self.replay().await {
+                    tracing::warn!("tangle stream replay failed: {}", e.message);
+                    return None;
+                }
+                continue;
+            }
+            match self.receiver.recv().await {
+                Ok(batch) => {
+                    let last_seq = self.last_seq;
+                    if batch.through <= last_seq {
+                        continue;
+                    }
+                    // something between what we sent and this batch was
+                    // published while we were not listening
+                    if batch.after > last_seq {
+                        self.catching_up = true;
+                        continue;
+                    }
+                    self.backlog
+                        .extend(batch.rows.iter().filter(|r| r.seq > last_seq).cloned());
+                    self.last_seq = batch.through;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => self.catching_up = true,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Queues the next blocks after `last_seq` up to the watermark taken
+    /// when the replay started, and stops catching up once past it.
+    async fn replay(&mut self) -> Result<(), AppError> {
+        let last_seq = self.last_seq;
+        let replay_to = match self.replay_to {
+            Some(seq) => seq,
+            None => match repo::committed_seq(&self.pool, last_seq).await? {
+                Some(seq) => *self.replay_to.insert(seq),
+                None => {
+                    self.catching_up = false;
+                    return Ok(());
+                }
+            },
+        };
+        let rows = repo::export_batch(&self.pool, last_seq, REPLAY_BATCH).await?;
+        let rows: Vec<_> = rows
+            .into_iter()
+            .take_while(|r| r.seq <= replay_to)
+            .map(Arc::new)
+            .collect();
+        match rows.last() {
+            Some(row) => self.last_seq = row.seq,
+            None => {
+                self.last_seq = replay_to;
+                self.replay_to = None;
+                self.catching_up = false;
+            }
+        }
+        self.backlog.extend(rows);
+        Ok(())
+    }
+}
+
+/// In-memory counterpart of the `public_key`, `contains` and `path` filters
+/// of `repo::list_blocks`.
+fn matches(filter: &BlockFilter, block: &TangleBlockRow) -> bool {
+    if filter
+        .public_key
+        .as_ref()
+        .is_some_and(|k| *k != block.public_key)
+    {
+        return false;
+    }
+    if filter
+        .contains
+        .as_ref()
+        .is_some_and(|c| !json_contains(&block.data, c))
+    {
+        return false;
+    }
+    match (&filter.path, &filter.path_value) {
+        (Some(path), Some(value)) => json_at(&block.data, path) == Some(value),
+        _ => true,
+    }
+}
+
+/// Postgres `@>` on `jsonb`, without the top-level array-contains-scalar case.
+fn json_contains(doc: &serde_json::Value, pattern: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (doc, pattern) {
+        (Value::Object(d), Value::Object(p)) => p
+            .iter()
+            .all(|(k, pv)| d.get(k).is_some_and(|dv| json_contains(dv, pv))),
+        (Value::Array(d), Value::Array(p)) => {
+            p.iter().all(|pv| d.iter().any(|dv| json_contains(dv, pv)))
+        }
+        (d, p) => d == p,
+    }
+}
+
+/// Postgres `#>`: object keys, or array indexes given as numbers.
+fn json_at<'a>(doc: &'a serde_json::Value, path: &[String]) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(doc, |v, key| match v {
+        serde_json::Value::Object(o) => o.get(key),
+        serde_json::Value::Array(a) => key.parse::<usize>().ok().and_then(|i| a.get(i)),
+        _ => None,
+    })
+}
@@ -0,0 +1,225 @@
+use crate::http_error::AppError;
+use crate::plugins::tangle::config::TangleConfig;
+use crate::plugins::tangle::models::{
+    BlockFetch, TangleBlockCreate, TangleImportReport, TangleSyncReport, TipExchange,
+    TipExchangeResponse,
+};
+use crate::plugins::tangle::schema::SchemaRegistry;
+use crate::plugins::tangle::{repo, service};
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// How many tips are exchanged with a peer.
+pub const TIP_EXCHANGE_LIMIT: i64 = 1000;
+/// Upper bound on blocks pulled from one peer in a single sync.
+const MAX_FETCH_PER_SYNC: usize = 10_000;
+/// How many blocks one `/tangle/sync/blocks` request may ask for.
+pub const FETCH_BATCH: usize = 500;
+
+/// Pulls blocks this node is missing from its configured peers.
+///
+/// A sync posts our tips to `/tangle/sync/tips`, then walks back from every
+/// peer tip we do not hold, fetching blocks from `/tangle/sync/blocks` in
+/// batches of [`FETCH_BATCH`] until it reaches ones we already have. The
+/// fetched blocks are stored parents-first through the same
+/// validation path as `POST /tangle/blocks`.
+pub struct TangleSync {
+    pool: PgPool,
+    config: Arc<TangleConfig>,
+    schemas: Arc<SchemaRegistry>,
+    http: reqwest::Client,
+}
+
+impl TangleSync {
+    pub fn new(pool: PgPool, config: Arc<TangleConfig>, schemas: Arc<SchemaRegistry>) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self {
+            pool,
+            config,
+            schemas,
+            http,
+        }
+    }
+
+    /// Starts the background loop if peers and an interval are configured.
+    pub fn spawn(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.config.peers.is_empty() || self.config.sync_interval_secs == 0 {
+            return None;
+        }
+        let sync = self.clone();
+        let every = Duration::from_secs(self.config.sync_interval_secs);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                for report in sync.sync_all().await {
+                    match &report.error {
+                        Some(e) => tracing::warn!("tangle sync with {} failed: {}", report.peer, e),
+                        None if report.ingest.imported > 0 => tracing::info!(
+                            "tangle sync pulled {} blocks from {}",
+                            report.ingest.imported,
+                            report.peer
+                        ),
+                        None => {}
+                    }
+                }
+            }
+        }))
+    }
+
+    pub async fn sync_all(&self) -> Vec<TangleSyncReport> {
+        let mut reports = Vec::with_capacity(self.config.peers.len());
+        for peer in &self.config.peers {
+            let report = match self.sync_with_peer(peer).await {
+                Ok(report) => report,
+                Err(e) => TangleSyncReport {
+                    peer: peer.clone(),
+                    peer_missing: 0,
+                    error: Some(e.message),
+                    ingest: TangleImportReport::default(),
+                },
+            };
+            reports.push(report);
+        }
+        reports
+    }
+
+    pub async fn sync_with_peer(&self, peer: &str) -> Result<TangleSyncReport, AppError> {
+        let ours: Vec<Uuid> = repo::tips(&self.pool, TIP_EXCHANGE_LIMIT)
+            .await?
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        let exchange: TipExchangeResponse = self
+            .request(self.http.post(format!("{}/tangle/sync/tips", peer)))
+            .json(&TipExchange { tips: ours })
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(peer_error)?
+            .json()
+            .await
+            .map_err(peer_error)?;
+
+        let fetched = self.fetch_missing(peer, exchange.tips).await?;
+        let mut report = TangleSyncReport {
+            peer: peer.to_string(),
+            peer_missing: exchange.unknown.len(),
+            error: None,
+            ingest: TangleImportReport::default(),
+        };
+        self.store_parents_first(fetched, &mut report.ingest).await;
+        Ok(report)
+    }
+
+    /// Fetches `wanted` and every ancestor of them we do not hold, one
+    /// generation per round trip. Blocks the peer does not return are left
+    /// for ingest to treat as missing parents.
+    async fn fetch_missing(
+        &self,
+        peer: &str,
+        wanted: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, TangleBlockCreate>, AppError> {
+        let mut fetched: HashMap<Uuid, TangleBlockCreate> = HashMap::new();
+        let mut requested: HashSet<Uuid> = HashSet::new();
+        let mut queue = repo::find_missing_parents(&self.pool, &wanted).await?;
+        while !queue.is_empty() {
+            let budget = MAX_FETCH_PER_SYNC.saturating_sub(requested.len());
+            if budget == 0 {
+                tracing::warn!(
+                    "tangle sync with {} stopped after {} blocks",
+                    peer,
+                    fetched.len()
+                );
+                break;
+            }
+            let mut ids = Vec::new();
+            while ids.len() < budget.min(FETCH_BATCH)
+                && let Some(id) = queue.pop()
+            {
+                if requested.insert(id) {
+                    ids.push(id);
+                }
+            }
+            if ids.is_empty() {
+                break;
+            }
+            let blocks: Vec<TangleBlockCreate> = self
+                .request(self.http.post(format!("{}/tangle/sync/blocks", peer)))
+                .json(&BlockFetch { ids })
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(peer_error)?
+                .json()
+                .await
+                .map_err(peer_error)?;
+            let mut parents: Vec<Uuid> = Vec::new();
+            for block in blocks {
+                let Some(id) = block.id.filter(|id| requested.contains(id)) else {
+                    continue;
+                };
+                parents.extend(block.parents.iter().filter_map(|p| Uuid::parse_str(p).ok()));
+                fetched.insert(id, block);
+            }
+            queue.extend(
+                repo::find_missing_parents(&self.pool, &parents)
+                    .await?
+                    .into_iter()
+                    .filter(|id| !requested.contains(id)),
+            );
+        }
+        Ok(fetched)
+    }
+
+    /// Stores fetched blocks once none of their parents are still waiting.
+    async fn store_parents_first(
+        &self,
+        mut remaining: HashMap<Uuid, TangleBlockCreate>,
+        report: &mut TangleImportReport,
+    ) {
+        loop {
+            let ready: Vec<Uuid> = remaining
+                .iter()
+                .filter(|(_, block)| {
+                    block
+                        .parents
+                        .iter()
+                        .all(|p| Uuid::parse_str(p).map_or(true, |p| !remaining.contains_key(&p)))
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for id in ready {
+                let Some(block) = remaining.remove(&id) else {
+                    continue;
+                };
+                let outcome =
+                    service::ingest_block(&self.pool, &self.config, &self.schemas, block).await;
+                service::record_ingest(report, None, Some(id), outcome);
+            }
+        }
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.config.peer_api_key {
+            Some(key) => builder.header("x-api-key", key),
+            None => builder,
+        }
+    }
+}
+
+fn peer_error(e: reqwest::Error) -> AppError {
+    AppError::new(StatusCode::BAD_GATEWAY, format!("peerRequestFailed: {}", e))
+        .with_code("peer_unavailable")
+}
@@ -0,0 +1,47 @@
+use crate::plugins::tangle::service::parse_parents;
+use crate::plugins::tangle::tips::TipStrategy;
+use axum::http::StatusCode;
+use uuid::Uuid;
+
+#[test]
+fn parse_parents_accepts_distinct_uuids() {
+    let a = Uuid::new_v4();
+    let b = Uuid::new_v4();
+    let parsed = parse_parents(None, &[a.to_string(), b.to_string()]).expect("valid parents");
+    assert_eq!(parsed, vec![a, b]);
+}
+
+#[test]
+fn parse_parents_accepts_genesis_block() {
+    let parsed = parse_parents(Some(Uuid::new_v4()), &[]).expect("no parents is valid");
+    assert!(parsed.is_empty());
+}
+
+#[test]
+fn parse_parents_rejects_non_uuid() {
+    let err = parse_parents(None, &["p1".to_string()]).unwrap_err();
+    assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    assert_eq!(err.code.as_deref(), Some("invalid_parent"));
+}
+
+#[test]
+fn parse_parents_rejects_self_reference() {
+    let id = Uuid::new_v4();
+    let err = parse_parents(Some(id), &[id.to_string()]).unwrap_err();
+    assert_eq!(err.code.as_deref(), Some("self_parent"));
+}
+
+#[test]
+fn parse_parents_rejects_duplicates() {
+    let a = Uuid::new_v4();
+    let err = parse_parents(None, &[a.to_string(), a.to_string()]).unwrap_err();
+    assert_eq!(err.code.as_deref(), Some("duplicate_parent"));
+}
+
+#[test]
+fn tip_strategy_parses_known_names_only() {
+    assert_eq!("uniform".parse(), Ok(TipStrategy::Uniform));
+    assert_eq!("weighted".parse(), Ok(TipStrategy::Weighted));
+    assert_eq!("recent".parse(), Ok(TipStrategy::Recent));
+    assert_eq!("Recent".parse::<TipStrategy>(), Err(()));
+}
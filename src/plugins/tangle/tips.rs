@@ -0,0 +1,100 @@
+use crate::http_error::AppError;
+use crate::plugins::tangle::models::TangleBlockRow;
+use crate::plugins::tangle::repo;
+use rand::SeedableRng;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How many tips are considered when sampling.
+const TIP_POOL_SIZE: i64 = 1000;
+/// How far below a tip a weighted walk starts.
+const WALK_DEPTH: i32 = 10;
+
+/// How `GET /tangle/tips/select` picks parents for a new block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipStrategy {
+    /// Uniformly random tips.
+    Uniform,
+    /// Random walks from below the tips towards them, stepping to each child
+    /// with probability proportional to its cumulative weight, so new blocks
+    /// keep approving the heavy part of the DAG.
+    Weighted,
+    /// The newest tips.
+    Recent,
+}
+
+impl std::str::FromStr for TipStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(TipStrategy::Uniform),
+            "weighted" => Ok(TipStrategy::Weighted),
+            "recent" => Ok(TipStrategy::Recent),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Picks up to `count` distinct tips.
+pub async fn select_tips(
+    pool: &PgPool,
+    strategy: TipStrategy,
+    count: usize,
+) -> Result<Vec<TangleBlockRow>, AppError> {
+    if strategy == TipStrategy::Recent {
+        return repo::tips(pool, count as i64).await;
+    }
+
+    let tips = repo::tips(pool, TIP_POOL_SIZE).await?;
+    let mut rng = StdRng::from_entropy();
+    if strategy == TipStrategy::Uniform || tips.len() <= count {
+        let mut picked: Vec<TangleBlockRow> = tips;
+        picked.shuffle(&mut rng);
+        picked.truncate(count);
+        return Ok(picked);
+    }
+
+    let tip_ids: Vec<Uuid> = tips.iter().map(|t| t.id).collect();
+    let mut selected: Vec<Uuid> = Vec::with_capacity(count);
+    // walks may land on the same tip; give up after a few extra attempts
+    for _ in 0..count * 4 {
+        if selected.len() == count {
+            break;
+        }
+        let start = tip_ids
+            .choose(&mut rng)
+            .copied()
+            .expect("tips is not empty");
+        let tip = weighted_walk(pool, &mut rng, start).await?;
+        if !selected.contains(&tip) {
+            selected.push(tip);
+        }
+    }
+
+    let mut rows = repo::find_blocks(pool, &selected).await?;
+    rows.sort_by_key(|r| selected.iter().position(|id| *id == r.id));
+    Ok(rows)
+}
+
+/// Walks from the block `WALK_DEPTH` hops below `from` back up to a tip.
+async fn weighted_walk(pool: &PgPool, rng: &mut StdRng, from: Uuid) -> Result<Uuid, AppError> {
+    let mut current = repo::ancestors(pool, from, WALK_DEPTH)
+        .await?
+        .last()
+        .map(|r| r.id)
+        .unwrap_or(from);
+    loop {
+        let children = repo::child_weights(pool, current).await?;
+        if children.is_empty() {
+            return Ok(current);
+        }
+        let weights = WeightedIndex::new(children.iter().map(|(_, w)| *w)).map_err(|e| {
+            AppError::new(axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+        current = children[weights.sample(rng)].0;
+    }
+}
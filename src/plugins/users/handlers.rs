@@ -1,7 +1,7 @@
 use crate::cache::DynCache;
 use crate::http_error::AppError;
-use crate::plugins::auth::handlers::AuthUser;
-use crate::plugins::users::models::{CreateUser, UpdateUser, UserDto};
+use crate::plugins::auth::rbac::{ManageUsers, RequirePermission};
+use crate::plugins::users::models::{CreateUser, RoleDto, UpdateUser, UserDto};
 use crate::plugins::users::repo;
 use axum::http::StatusCode;
 use axum::{Extension, Json, extract::Path};
@@ -31,7 +31,6 @@ pub async fn get_user(
     pool: PgPool,
     Path(id): Path<Uuid>,
 ) -> Result<Json<UserDto>, AppError> {
-    
     if let Some(cache) = cache_opt.as_ref().map(|c| c.clone()) {
         let key = format!("user:{}", id);
         let maybe_bytes_res = cache.get(&key).await;
@@ -62,7 +61,7 @@ pub async fn update_user(
     let new_username = payload.username.unwrap_or(current.username);
     let new_email = payload.email.unwrap_or(current.email);
     let updated = repo::update_user(&pool, id, &new_username, &new_email).await?;
-    
+
     if let Some(cache) = cache_opt.as_ref().map(|c| c.clone()) {
         let key = format!("user:{}", id);
         let _ = cache.delete(&key).await;
@@ -86,16 +85,9 @@ pub async fn delete_user(
 pub async fn grant_admin(
     Extension(cache_opt): Extension<Option<DynCache>>,
     pool: PgPool,
-    auth: AuthUser,
+    _auth: RequirePermission<ManageUsers>,
     Path(id): Path<Uuid>,
 ) -> Result<StatusCode, AppError> {
-    let is_req_admin = repo::is_admin(&pool, auth.user_id).await?;
-    if !is_req_admin {
-        return Err(AppError::new(
-            StatusCode::FORBIDDEN,
-            "onlyAdminCanGrantAdmin",
-        ));
-    }
     repo::set_admin(&pool, id, true).await?;
     if let Some(cache) = cache_opt.as_ref().map(|c| c.clone()) {
         let key = format!("user:{}", id);
@@ -107,16 +99,9 @@ pub async fn grant_admin(
 pub async fn create_admin(
     Extension(_cache_opt): Extension<Option<DynCache>>,
     pool: PgPool,
-    auth: AuthUser,
+    _auth: RequirePermission<ManageUsers>,
     payload: CreateUser,
 ) -> Result<Json<UserDto>, AppError> {
-    let is_req_admin = repo::is_admin(&pool, auth.user_id).await?;
-    if !is_req_admin {
-        return Err(AppError::new(
-            StatusCode::FORBIDDEN,
-            "onlyAdminCanCreateAdminAccounts",
-        ));
-    }
     if !payload.email.contains('@') {
         return Err(AppError::new(StatusCode::BAD_REQUEST, "invalidEmail"));
     }
@@ -131,6 +116,43 @@ pub async fn create_admin(
         true,
     )
     .await?;
-    
+
     Ok(Json(dto))
 }
+
+pub async fn list_roles(
+    Extension(pool): Extension<PgPool>,
+    _auth: RequirePermission<ManageUsers>,
+) -> Result<Json<Vec<RoleDto>>, AppError> {
+    Ok(Json(repo::list_roles(&pool).await?))
+}
+
+pub async fn get_user_roles(
+    Extension(pool): Extension<PgPool>,
+    _auth: RequirePermission<ManageUsers>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<String>>, AppError> {
+    repo::get_user(&pool, id).await?;
+    Ok(Json(repo::user_roles(&pool, id).await?))
+}
+
+/// Takes effect when the user next logs in or refreshes their token.
+pub async fn grant_role(
+    Extension(pool): Extension<PgPool>,
+    auth: RequirePermission<ManageUsers>,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> Result<StatusCode, AppError> {
+    repo::grant_role(&pool, id, &role).await?;
+    tracing::info!("user {} granted role {} to {}", auth.user.user_id, role, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn revoke_role(
+    Extension(pool): Extension<PgPool>,
+    auth: RequirePermission<ManageUsers>,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> Result<StatusCode, AppError> {
+    repo::revoke_role(&pool, id, &role).await?;
+    tracing::info!("user {} revoked role {} from {}", auth.user.user_id, role, id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -20,3 +20,10 @@ pub struct UpdateUser {
     pub username: Option<String>,
     pub email: Option<String>,
 }
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct RoleDto {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
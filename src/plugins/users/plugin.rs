@@ -1,9 +1,13 @@
 use crate::cache::DynCache;
 use crate::kernel::Plugin;
+use crate::plugins::auth::policy::{Access, AccessRule};
+use crate::plugins::auth::rbac::{ManageUsers, Permission, RequirePermission};
 use crate::plugins::users::handlers::{
-    create_admin, create_user, delete_user, get_user, grant_admin, list_users, update_user,
+    create_admin, create_user, delete_user, get_user, get_user_roles, grant_admin, grant_role,
+    list_roles, list_users, revoke_role, update_user,
 };
 use crate::plugins::users::models::{CreateUser, UpdateUser};
+use axum::http::Method;
 use axum::{
     Json, Router,
     extract::Extension,
@@ -82,32 +86,48 @@ impl Plugin for UsersPlugin {
                 post(
                     move |ext: Extension<Option<DynCache>>,
                           Path(id): Path<uuid::Uuid>,
-                          auth: crate::plugins::auth::handlers::AuthUser| {
+                          auth: RequirePermission<ManageUsers>| {
                         let pool = p_grant.clone();
                         async move { grant_admin(ext, pool, auth, Path(id)).await }
                     },
-                )
-                .layer(axum::middleware::from_fn(
-                    crate::plugins::auth::middleware::require_auth,
-                )),
+                ),
             )
             .route(
                 "/admin",
                 post(
                     move |ext: Extension<Option<DynCache>>,
-                          auth: crate::plugins::auth::handlers::AuthUser,
+                          auth: RequirePermission<ManageUsers>,
                           Json(payload): Json<CreateUser>| {
                         let pool = p_admin.clone();
                         async move { create_admin(ext, pool, auth, payload).await }
                     },
-                )
-                .layer(axum::middleware::from_fn(
-                    crate::plugins::auth::middleware::require_auth,
-                )),
+                ),
             )
+            .route("/roles", get(list_roles))
+            .route("/:id/roles", get(get_user_roles))
+            .route("/:id/roles/:role", put(grant_role).delete(revoke_role))
+            .layer(Extension(self.pool.clone()))
     }
 
     fn name(&self) -> &'static str {
         "users"
     }
+
+    fn access_rules(&self) -> Vec<AccessRule> {
+        vec![
+            AccessRule::on(
+                Method::POST,
+                "/:id/grant_admin",
+                Access::Permission(ManageUsers::NAME),
+            ),
+            AccessRule::on(
+                Method::POST,
+                "/admin",
+                Access::Permission(ManageUsers::NAME),
+            ),
+            AccessRule::any("/roles", Access::Permission(ManageUsers::NAME)),
+            AccessRule::any("/:id/roles", Access::Permission(ManageUsers::NAME)),
+            AccessRule::any("/:id/roles/:role", Access::Permission(ManageUsers::NAME)),
+        ]
+    }
 }
@@ -1,5 +1,5 @@
 use crate::http_error::AppError;
-use crate::plugins::users::models::UserDto;
+use crate::plugins::users::models::{RoleDto, UserDto};
 use axum::http::StatusCode;
 use bcrypt::{DEFAULT_COST, hash};
 use sqlx::PgPool;
@@ -38,14 +38,23 @@ pub async fn insert_user_with_admin(
 ) -> Result<UserDto, AppError> {
     let password_hash = hash(password, DEFAULT_COST)
         .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
     let row = sqlx::query("INSERT INTO users (username, email, password_hash, is_admin) VALUES ($1, $2, $3, $4) RETURNING id, username, email")
         .bind(username)
         .bind(email)
         .bind(&password_hash)
         .bind(is_admin)
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await
         .map_err(AppError::from)?;
+    if is_admin {
+        sqlx::query("INSERT INTO user_roles (user_id, role) VALUES ($1, 'admin')")
+            .bind(row.get::<Uuid, _>("id"))
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::from)?;
+    }
+    tx.commit().await.map_err(AppError::from)?;
 
     Ok(UserDto {
         id: row.get("id"),
@@ -126,10 +135,79 @@ pub async fn is_admin(pool: &PgPool, id: Uuid) -> Result<bool, AppError> {
     Ok(row)
 }
 
+/// Grants or takes away the `admin` role, keeping `is_admin` in step.
 pub async fn set_admin(pool: &PgPool, id: Uuid, admin: bool) -> Result<(), AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::from)?;
     sqlx::query("UPDATE users SET is_admin = $1 WHERE id = $2")
         .bind(admin)
         .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+    let roles = if admin {
+        "INSERT INTO user_roles (user_id, role) SELECT id, 'admin' FROM users WHERE id = $1 ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM user_roles WHERE user_id = $1 AND role = 'admin'"
+    };
+    sqlx::query(roles)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from)?;
+    tx.commit().await.map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn user_roles(pool: &PgPool, id: Uuid) -> Result<Vec<String>, AppError> {
+    sqlx::query_scalar::<_, String>("SELECT role FROM user_roles WHERE user_id = $1 ORDER BY role")
+        .bind(id)
+        .fetch_all(pool)
+        .await
+        .map_err(AppError::from)
+}
+
+pub async fn list_roles(pool: &PgPool) -> Result<Vec<RoleDto>, AppError> {
+    sqlx::query_as::<_, RoleDto>(
+        "SELECT r.name, r.description, COALESCE(array_agg(rp.permission ORDER BY rp.permission) FILTER (WHERE rp.permission IS NOT NULL), '{}') AS permissions FROM roles r LEFT JOIN role_permissions rp ON rp.role = r.name GROUP BY r.name, r.description ORDER BY r.name",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::from)
+}
+
+async fn role_exists(pool: &PgPool, role: &str) -> Result<bool, AppError> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM roles WHERE name = $1)")
+        .bind(role)
+        .fetch_one(pool)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Gives user `id` the role; granting one already held is a no-op.
+pub async fn grant_role(pool: &PgPool, id: Uuid, role: &str) -> Result<(), AppError> {
+    if !role_exists(pool, role).await? {
+        return Err(AppError::new(StatusCode::NOT_FOUND, "roleNotFound").with_code("not_found"));
+    }
+    get_user(pool, id).await?;
+    if role == "admin" {
+        return set_admin(pool, id, true).await;
+    }
+    sqlx::query("INSERT INTO user_roles (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(id)
+        .bind(role)
+        .execute(pool)
+        .await
+        .map_err(AppError::from)?;
+    Ok(())
+}
+
+pub async fn revoke_role(pool: &PgPool, id: Uuid, role: &str) -> Result<(), AppError> {
+    if role == "admin" {
+        return set_admin(pool, id, false).await;
+    }
+    sqlx::query("DELETE FROM user_roles WHERE user_id = $1 AND role = $2")
+        .bind(id)
+        .bind(role)
         .execute(pool)
         .await
         .map_err(AppError::from)?;
@@ -122,3 +122,95 @@ pub async fn spawn_app_with_plugins(
     });
     Ok((format!("http://{}", addr), server_handle))
 }
+
+#[allow(dead_code)]
+pub fn test_keypair(seed: u8) -> ed25519_dalek::Keypair {
+    let secret = ed25519_dalek::SecretKey::from_bytes(&[seed; 32]).expect("secret key");
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    ed25519_dalek::Keypair { secret, public }
+}
+
+/// The canonical v1 message a plain block (no `supersedes`) is signed over.
+#[allow(dead_code)]
+pub fn canonical_signing_bytes(
+    id: uuid::Uuid,
+    parents: &[uuid::Uuid],
+    data: &serde_json::Value,
+    timestamp_ms: i64,
+) -> Vec<u8> {
+    ecoblock_api_kernel::plugins::tangle::crypto::SigningPayload {
+        id,
+        parents,
+        data,
+        timestamp_ms,
+        supersedes: None,
+        tombstone: false,
+    }
+    .canonical_bytes()
+}
+
+/// Builds a `POST /tangle/blocks` body signed over the canonical v1 payload.
+#[allow(dead_code)]
+pub fn signed_block(
+    keypair: &ed25519_dalek::Keypair,
+    id: uuid::Uuid,
+    parents: &[uuid::Uuid],
+    data: serde_json::Value,
+) -> serde_json::Value {
+    use base64::Engine as _;
+    use ed25519_dalek::Signer;
+    let engine = base64::engine::general_purpose::STANDARD;
+    let timestamp_ms = chrono::Utc::now().timestamp_millis();
+    let msg = canonical_signing_bytes(id, parents, &data, timestamp_ms);
+    let signature = keypair.sign(&msg).to_bytes();
+    let parents: Vec<String> = parents.iter().map(|p| p.to_string()).collect();
+    serde_json::json!({
+        "id": id,
+        "parents": parents,
+        "data": data,
+        "signature": engine.encode(signature),
+        "public_key": engine.encode(keypair.public.to_bytes()),
+        "timestamp_ms": timestamp_ms
+    })
+}
+
+/// Builds a signed block that supersedes (or, with `tombstone`, deletes) `target`.
+#[allow(dead_code)]
+pub fn signed_superseding_block(
+    keypair: &ed25519_dalek::Keypair,
+    id: uuid::Uuid,
+    target: uuid::Uuid,
+    data: serde_json::Value,
+    tombstone: bool,
+) -> serde_json::Value {
+    use base64::Engine as _;
+    use ecoblock_api_kernel::plugins::tangle::crypto::{
+        SigScheme, SigningPayload, signing_message,
+    };
+    use ed25519_dalek::Signer;
+    let engine = base64::engine::general_purpose::STANDARD;
+    let timestamp_ms = chrono::Utc::now().timestamp_millis();
+    let parents = [target];
+    let msg = signing_message(
+        SigScheme::CanonicalV1,
+        &SigningPayload {
+            id,
+            parents: &parents,
+            data: &data,
+            timestamp_ms,
+            supersedes: Some(target),
+            tombstone,
+        },
+    );
+    let signature = keypair.sign(&msg).to_bytes();
+    serde_json::json!({
+        "id": id,
+        "parents": [target.to_string()],
+        "data": data,
+        "signature": engine.encode(signature),
+        "public_key": engine.encode(keypair.public.to_bytes()),
+        "timestamp_ms": timestamp_ms,
+        "supersedes": target,
+        "tombstone": tombstone
+    })
+}
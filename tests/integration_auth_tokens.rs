@@ -0,0 +1,112 @@
+use ecoblock_api_kernel::plugins::auth::tokens::{Claims, TokenService};
+use std::time::Duration;
+use uuid::Uuid;
+
+const RSA_PRIVATE: &[u8] = include_bytes!("fixtures/jwt/rsa_private.pem");
+const RSA_PUBLIC: &[u8] = include_bytes!("fixtures/jwt/rsa_public.pem");
+const ED_PRIVATE: &[u8] = include_bytes!("fixtures/jwt/ed25519_private.pem");
+const ED_PUBLIC: &[u8] = include_bytes!("fixtures/jwt/ed25519_public.pem");
+const ED_OLD_PRIVATE: &[u8] = include_bytes!("fixtures/jwt/ed25519_old_private.pem");
+const ED_OLD_PUBLIC: &[u8] = include_bytes!("fixtures/jwt/ed25519_old_public.pem");
+
+fn issue(service: &TokenService, user: Uuid) -> String {
+    service
+        .issue_access(user, Vec::new(), None, false)
+        .unwrap_or_else(|e| panic!("{}", e.message))
+}
+
+/// The claims, or the error code.
+fn verify(service: &TokenService, token: &str) -> Result<Claims, String> {
+    service
+        .verify(token)
+        .map_err(|e| e.code.unwrap_or_default())
+}
+
+fn header_kid(token: &str) -> Option<String> {
+    jsonwebtoken::decode_header(token).unwrap().kid
+}
+
+#[test]
+fn token_service_rotates_keys_by_kid() -> anyhow::Result<()> {
+    let user = Uuid::new_v4();
+
+    let old = TokenService::hs256("2024", b"old-secret");
+    let current =
+        TokenService::hs256("2025", b"new-secret").with_previous_key("2024", b"old-secret")?;
+    let token = issue(&current, user);
+    assert_eq!(header_kid(&token).as_deref(), Some("2025"));
+    assert_eq!(verify(&current, &token).unwrap().sub, user.to_string());
+    // issued before the rotation
+    assert_eq!(
+        verify(&current, &issue(&old, user)).unwrap().sub,
+        user.to_string()
+    );
+    // signed with the old secret but claiming the new kid
+    let forged = jsonwebtoken::encode(
+        &jsonwebtoken::Header {
+            kid: Some("2025".to_string()),
+            ..Default::default()
+        },
+        &serde_json::json!({"sub": user, "exp": 4_000_000_000u64, "iat": 0, "jti": "x"}),
+        &jsonwebtoken::EncodingKey::from_secret(b"old-secret"),
+    )?;
+    assert_eq!(verify(&current, &forged).unwrap_err(), "invalid_token");
+    let unknown = issue(&TokenService::hs256("2023", b"older-secret"), user);
+    assert!(verify(&current, &unknown).is_err());
+
+    let ed_old = TokenService::eddsa("ed-1", ED_OLD_PRIVATE, ED_OLD_PUBLIC)?;
+    let ed = TokenService::eddsa("ed-2", ED_PRIVATE, ED_PUBLIC)?
+        .with_previous_key("ed-1", ED_OLD_PUBLIC)?;
+    assert!(verify(&ed, &issue(&ed, user)).is_ok());
+    assert!(verify(&ed, &issue(&ed_old, user)).is_ok());
+    assert!(verify(&ed_old, &issue(&ed, user)).is_err());
+    Ok(())
+}
+
+#[test]
+fn token_service_verifies_rs256_without_algorithm_confusion() -> anyhow::Result<()> {
+    let user = Uuid::new_v4();
+    let rs = TokenService::rs256("rsa-1", RSA_PRIVATE, RSA_PUBLIC)?;
+    let token = issue(&rs, user);
+    assert_eq!(
+        jsonwebtoken::decode_header(&token)?.alg,
+        jsonwebtoken::Algorithm::RS256
+    );
+    assert_eq!(verify(&rs, &token).unwrap().sub, user.to_string());
+
+    // HS256 keyed with the public key must not pass as RS256
+    let confused = issue(&TokenService::hs256("rsa-1", RSA_PUBLIC), user);
+    assert!(verify(&rs, &confused).is_err());
+    Ok(())
+}
+
+#[test]
+fn token_service_checks_issuer_audience_and_leeway() -> anyhow::Result<()> {
+    let user = Uuid::new_v4();
+    let service = TokenService::hs256("k", b"secret-1")
+        .with_issuer(Some("ecoblock".to_string()))
+        .with_audience(Some("ecoblock-api".to_string()));
+    let claims = verify(&service, &issue(&service, user)).unwrap();
+    assert_eq!(claims.iss.as_deref(), Some("ecoblock"));
+    assert_eq!(claims.aud.as_deref(), Some("ecoblock-api"));
+
+    // without the claims, or with other values
+    let bare = issue(&TokenService::hs256("k", b"secret-1"), user);
+    assert!(verify(&service, &bare).is_err());
+    let other = TokenService::hs256("k", b"secret-1")
+        .with_issuer(Some("ecoblock".to_string()))
+        .with_audience(Some("someone-else".to_string()));
+    let other = issue(&other, user);
+    assert!(verify(&service, &other).is_err());
+
+    // an expired token passes within the leeway only
+    let expired =
+        TokenService::hs256("k", b"secret-2").with_ttls(Duration::ZERO, Duration::from_secs(60));
+    let expired = issue(&expired, user);
+    std::thread::sleep(Duration::from_millis(1100));
+    let lenient = TokenService::hs256("k", b"secret-2");
+    assert!(verify(&lenient, &expired).is_ok());
+    let strict = TokenService::hs256("k", b"secret-2").with_leeway(Duration::ZERO);
+    assert_eq!(verify(&strict, &expired).unwrap_err(), "token_expired");
+    Ok(())
+}
@@ -32,5 +32,11 @@ async fn redis_cache_get_set_delete_smoke() -> anyhow::Result<()> {
     cache.delete(key).await?;
     let got2 = cache.get(key).await?;
     assert_eq!(got2, None);
+
+    let counter = "test:redis:smoke:counter";
+    cache.delete(counter).await?;
+    assert_eq!(cache.incr(counter, Some(Duration::from_secs(5))).await?, 1);
+    assert_eq!(cache.incr(counter, Some(Duration::from_secs(5))).await?, 2);
+    cache.delete(counter).await?;
     Ok(())
 }
@@ -1,7 +1,8 @@
 mod common;
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
-use common::{create_test_db_and_pool, spawn_app_with_plugins};
+use common::{
+    create_test_db_and_pool, signed_block, signed_superseding_block, spawn_app_with_plugins,
+    test_keypair,
+};
 use reqwest::StatusCode;
 use serde_json::Value;
 
@@ -19,53 +20,174 @@ async fn tangle_crud() -> anyhow::Result<()> {
     ];
     let (base, server_handle) = spawn_app_with_plugins(pool.clone(), plugins).await?;
     let client = reqwest::Client::new();
+    let keypair = test_keypair(11);
 
-    // create (omit id, let DB generate UUID)
-    let payload = serde_json::json!({
-        "parents": ["p1","p2"],
-        "data": {"hello":"world"},
-    "signature": BASE64_ENGINE.encode(b"sigbytes"),
-        "public_key": "pk"
-    });
+    // create
+    let block_uuid = uuid::Uuid::new_v4();
+    let payload = signed_block(
+        &keypair,
+        block_uuid,
+        &[],
+        serde_json::json!({"hello":"world"}),
+    );
 
     let create = client
-        .post(&format!("{}/tangle/blocks", base))
+        .post(format!("{}/tangle/blocks", base))
         .json(&payload)
         .send()
         .await?;
     assert_eq!(create.status(), StatusCode::OK);
     let created: Value = create.json().await?;
-    let block_id_str = created["id"].as_str().unwrap().to_string();
-    let block_uuid: uuid::Uuid = block_id_str.parse()?;
+    assert_eq!(created["id"].as_str().unwrap(), block_uuid.to_string());
+    assert_eq!(created["tombstone"], false);
 
     // get
     let one = client
-        .get(&format!("{}/tangle/{}", base, block_uuid))
+        .get(format!("{}/tangle/{}", base, block_uuid))
         .send()
         .await?;
     assert_eq!(one.status(), StatusCode::OK);
 
     // list
-    let list = client
-        .get(&format!("{}/tangle/blocks", base))
-        .send()
-        .await?;
+    let list = client.get(format!("{}/tangle/blocks", base)).send().await?;
     assert_eq!(list.status(), StatusCode::OK);
 
-    // update
+    // blocks are immutable over HTTP
     let upd = client
-        .put(&format!("{}/tangle/{}", base, block_uuid))
+        .put(format!("{}/tangle/{}", base, block_uuid))
         .json(&serde_json::json!({"data":{"foo":"bar"}}))
         .send()
         .await?;
-    assert_eq!(upd.status(), StatusCode::OK);
-
-    // delete
+    assert_eq!(upd.status(), StatusCode::METHOD_NOT_ALLOWED);
     let del = client
-        .delete(&format!("{}/tangle/{}", base, block_uuid))
+        .delete(format!("{}/tangle/{}", base, block_uuid))
+        .send()
+        .await?;
+    assert_eq!(del.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    // ... and in the database
+    let res = sqlx::query("UPDATE public.tangle_blocks SET data = '{}'::jsonb WHERE id = $1")
+        .bind(block_uuid)
+        .execute(&pool)
+        .await;
+    assert!(res.is_err(), "direct updates must be rejected");
+    let res = sqlx::query("DELETE FROM public.tangle_blocks WHERE id = $1")
+        .bind(block_uuid)
+        .execute(&pool)
+        .await;
+    assert!(res.is_err(), "direct deletes must be rejected");
+
+    // supersede with a new signed version
+    let v2 = uuid::Uuid::new_v4();
+    let res = client
+        .post(format!("{}/tangle/blocks", base))
+        .json(&signed_superseding_block(
+            &keypair,
+            v2,
+            block_uuid,
+            serde_json::json!({"foo":"bar"}),
+            false,
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let effective: Value = client
+        .get(format!("{}/tangle/{}/effective", base, block_uuid))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(effective["id"].as_str().unwrap(), v2.to_string());
+    assert_eq!(effective["data"], serde_json::json!({"foo":"bar"}));
+
+    // the original stays readable as-is
+    let original: Value = client
+        .get(format!("{}/tangle/{}", base, block_uuid))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(original["data"], serde_json::json!({"hello":"world"}));
+
+    // only the head of a chain can be superseded
+    let res = client
+        .post(format!("{}/tangle/blocks", base))
+        .json(&signed_superseding_block(
+            &keypair,
+            uuid::Uuid::new_v4(),
+            block_uuid,
+            serde_json::json!({"fork":true}),
+            false,
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "already_superseded");
+
+    // and only by the key that signed it
+    let res = client
+        .post(format!("{}/tangle/blocks", base))
+        .json(&signed_superseding_block(
+            &test_keypair(12),
+            uuid::Uuid::new_v4(),
+            v2,
+            serde_json::json!({"foo":"baz"}),
+            false,
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "superseder_key_mismatch");
+
+    // tombstone the chain
+    let v3 = uuid::Uuid::new_v4();
+    let res = client
+        .post(format!("{}/tangle/blocks", base))
+        .json(&signed_superseding_block(
+            &keypair,
+            v3,
+            v2,
+            Value::Null,
+            true,
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .get(format!("{}/tangle/{}/effective", base, v2))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::GONE);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "block_deleted");
+
+    let history: Vec<Value> = client
+        .get(format!("{}/tangle/{}/history", base, v3))
+        .send()
+        .await?
+        .json()
+        .await?;
+    let ids: Vec<&str> = history.iter().map(|b| b["id"].as_str().unwrap()).collect();
+    assert_eq!(
+        ids,
+        vec![block_uuid.to_string(), v2.to_string(), v3.to_string()]
+    );
+
+    // a tombstone without a target is meaningless
+    let mut orphan_tombstone = signed_block(&keypair, uuid::Uuid::new_v4(), &[], Value::Null);
+    orphan_tombstone["tombstone"] = true.into();
+    let res = client
+        .post(format!("{}/tangle/blocks", base))
+        .json(&orphan_tombstone)
         .send()
         .await?;
-    assert_eq!(del.status(), StatusCode::NO_CONTENT);
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = res.json().await?;
+    assert_eq!(body["code"], "invalid_supersession");
 
     server_handle.abort();
     let _ = server_handle.await;
@@ -70,7 +70,7 @@ async fn integration_create_admin_forbidden_for_non_admin() -> anyhow::Result<()
     let body: Value = create.json().await?;
     assert_eq!(
         body["error"].as_str().unwrap(),
-        "onlyAdminCanCreateAdminAccounts"
+        "permissionRequired: users:admin"
     );
     server_handle.abort();
     let _ = server_handle.await;